@@ -0,0 +1,101 @@
+use clap::ValueEnum;
+
+/// Case style applied to the words of a converted stem.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Case {
+    /// Keep ASCII casing and separators as written (romaji is lowercase)
+    #[default]
+    Preserve,
+    /// snake_case
+    Snake,
+    /// kebab-case
+    Kebab,
+    /// camelCase
+    Camel,
+    /// PascalCase
+    Pascal,
+    /// Title Case, words joined by the space separator
+    Title,
+}
+
+/// Split non-Japanese text into words at spaces, `_`, `-` and camel humps
+/// (`myFile` → `my` + `File`, `HTTPServer` → `HTTP` + `Server`).
+pub fn split_words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let chars: Vec<char> = s.chars().collect();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_whitespace() || c == '_' || c == '-' {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase()
+            && let Some(&prev) = i.checked_sub(1).map(|p| &chars[p])
+        {
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            let hump = prev.is_lowercase() || (prev.is_uppercase() && next_lower);
+            if hump && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Join `words` in the given case style. `separator` is used between
+/// words in Title Case; other styles have a fixed separator (or none).
+pub fn render(words: &[String], case: Case, separator: char) -> String {
+    let lower = |w: &String| w.to_lowercase();
+    let styled: Vec<String> = match case {
+        Case::Preserve => return words.concat(),
+        Case::Snake | Case::Kebab => words.iter().map(lower).collect(),
+        Case::Camel => words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| if i == 0 { lower(w) } else { capitalize(w) })
+            .collect(),
+        Case::Pascal | Case::Title => words.iter().map(|w| capitalize(w)).collect(),
+    };
+    let joiner = match case {
+        Case::Snake => "_".to_string(),
+        Case::Kebab => "-".to_string(),
+        Case::Title => separator.to_string(),
+        Case::Preserve | Case::Camel | Case::Pascal => String::new(),
+    };
+    styled.join(&joiner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_on_separators() {
+        assert_eq!(split_words("my file_name-v2"), ["my", "file", "name", "v2"]);
+    }
+
+    #[test]
+    fn split_on_camel_humps() {
+        assert_eq!(split_words("myFileName"), ["my", "File", "Name"]);
+        assert_eq!(split_words("HTTPServer"), ["HTTP", "Server"]);
+    }
+}
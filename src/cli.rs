@@ -1,10 +1,17 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+use romv::{Case, Encoding, FileType, Form, LongVowels, OnConflict, Sanitize, Script, System};
+
+use crate::format::Format;
+
 /// Rename Japanese filenames to romaji
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Files to rename. Reads from stdin if omitted and input is piped.
     pub files: Vec<PathBuf>,
 
@@ -12,15 +19,206 @@ pub struct Args {
     #[arg(short = 'y', long = "yes")]
     pub yes: bool,
 
+    /// Rename the contents of directories recursively
+    #[arg(short, long, overrides_with = "no_recursive")]
+    pub recursive: bool,
+
+    /// Do not rename directory contents (overrides a config file)
+    #[arg(long, overrides_with = "recursive")]
+    pub no_recursive: bool,
+
+    /// Descend at most N directory levels below each input (with -r)
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Also rename the directories along each path whose names change
+    #[arg(long, overrides_with = "no_full_path")]
+    pub full_path: bool,
+
+    /// Do not rename the directories along each path
+    #[arg(long, overrides_with = "full_path")]
+    pub no_full_path: bool,
+
+    /// Only rename paths whose name matches this glob (repeatable; a glob
+    /// with `/` is matched against the whole path)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Never rename paths whose name matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only rename paths whose name matches this regular expression
+    #[arg(long = "match", value_name = "REGEX")]
+    pub pattern: Option<String>,
+
+    /// Only rename files (f), directories (d) or symlinks (l)
+    #[arg(long = "type", value_enum, value_name = "TYPE")]
+    pub file_type: Option<FileType>,
+
     /// Confirm each rename interactively
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_interactive")]
     pub interactive: bool,
 
+    /// Do not confirm each rename
+    #[arg(long, overrides_with = "interactive")]
+    pub no_interactive: bool,
+
+    /// How to handle targets that exist or collide within the batch
+    #[arg(long, value_enum, default_value_t = OnConflict::default())]
+    pub on_conflict: OnConflict,
+
+    /// Output format of the plan
+    #[arg(long, value_enum, default_value_t = Format::default())]
+    pub format: Format,
+
+    /// Read NUL-separated paths from stdin (e.g. from `find -print0`)
+    #[arg(short = '0', long, conflicts_with = "files")]
+    pub null: bool,
+
+    /// Print renamed targets NUL-separated on stdout instead of the plan
+    #[arg(long)]
+    pub print0: bool,
+
+    /// Execute a previously exported (and possibly edited) plan
+    /// instead of converting FILES
+    #[arg(long, value_name = "FILE", conflicts_with_all = [
+        "files", "recursive", "full_path", "null", "include", "exclude", "pattern", "file_type",
+    ])]
+    pub plan: Option<PathBuf>,
+
     /// Show each operation
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_verbose")]
     pub verbose: bool,
 
+    /// Do not show each operation
+    #[arg(long, overrides_with = "verbose")]
+    pub no_verbose: bool,
+
     /// Character to replace spaces with
     #[arg(short, long, default_value = "_")]
     pub separator: char,
+
+    /// Character to put between Japanese words (words are joined if omitted)
+    #[arg(short, long)]
+    pub word_separator: Option<char>,
+
+    /// Case style of the converted name
+    #[arg(long, value_enum, default_value_t = Case::default())]
+    pub case: Case,
+
+    /// Romanization system for Japanese readings
+    #[arg(long, value_enum, default_value_t = System::default())]
+    pub system: System,
+
+    /// Spelling of long vowels: long o and u (おう, おお, うう) and any
+    /// vowel before ー; doubled ああ, いい and ええ are kept as written
+    /// [default: ou, or o with --system passport]
+    #[arg(long, value_enum)]
+    pub long_vowels: Option<LongVowels>,
+
+    /// Also transliterate these non-Japanese scripts (comma-separated)
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "SCRIPTS")]
+    pub scripts: Vec<Script>,
+
+    /// Also keep these multi-part extensions whole (comma-separated;
+    /// tar.gz, tar.bz2, tar.xz, tar.zst and d.ts always are)
+    #[arg(long, value_delimiter = ',', value_name = "EXT")]
+    pub multi_ext: Vec<String>,
+
+    /// Romanize Japanese in extensions too
+    #[arg(long, overrides_with = "no_romanize_ext")]
+    pub romanize_ext: bool,
+
+    /// Keep Japanese in extensions
+    #[arg(long, overrides_with = "romanize_ext")]
+    pub no_romanize_ext: bool,
+
+    /// Lowercase extensions (.JPG -> .jpg)
+    #[arg(long, overrides_with = "no_lowercase_ext")]
+    pub lowercase_ext: bool,
+
+    /// Keep the case of extensions
+    #[arg(long, overrides_with = "lowercase_ext")]
+    pub no_lowercase_ext: bool,
+
+    /// Replace characters that are unsafe in this kind of name, collapse
+    /// repeated separators and trim them from the ends
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub sanitize: Option<Sanitize>,
+
+    /// Truncate new names longer than this many bytes at a word boundary
+    /// [default: the filesystem's limit, usually 255]
+    #[arg(long, value_name = "BYTES")]
+    pub max_length: Option<usize>,
+
+    /// Replace a punctuation mark or symbol: FROM=TO, TO may be empty
+    /// (repeatable; e.g. --map '【=(' --map '※=')
+    #[arg(long, value_name = "FROM=TO", value_parser = romv::parse_mapping)]
+    pub map: Vec<(char, String)>,
+
+    /// Fail if a new name still contains non-ASCII characters, such as
+    /// CJK Extension B–G kanji (only a few common variants are read)
+    #[arg(long, overrides_with = "no_strict_ascii")]
+    pub strict_ascii: bool,
+
+    /// Allow non-ASCII characters in new names
+    #[arg(long, overrides_with = "strict_ascii")]
+    pub no_strict_ascii: bool,
+
+    /// Unicode normalization form of the new names
+    #[arg(long, value_enum, value_name = "FORM", default_value_t = Form::default())]
+    pub output_form: Form,
+
+    /// Only normalize names to the output form (e.g. NFD from macOS to
+    /// NFC), without romanizing them
+    #[arg(long)]
+    pub normalize_only: bool,
+
+    /// Decode filenames that are not valid UTF-8 from this encoding
+    #[arg(long, value_enum, value_name = "ENCODING")]
+    pub from_encoding: Option<Encoding>,
+
+    /// User dictionary of `surface<TAB>romaji` lines
+    /// [default: $XDG_CONFIG_HOME/romv/dict.tsv if it exists]
+    #[arg(long, value_name = "FILE")]
+    pub dict: Option<PathBuf>,
+
+    /// Apply the named profile from the config files
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Revert renames recorded in the journal
+    Undo(UndoArgs),
+    /// Inspect the configuration files
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the effective settings and the files they come from
+    Show {
+        /// Apply the named profile
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+pub struct UndoArgs {
+    /// Execute the undo (default is dry-run preview)
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// Number of most recent batches to undo
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub last: usize,
+
+    /// Journal to replay [default: $XDG_STATE_HOME/romv/journal.jsonl]
+    #[arg(long, value_name = "FILE")]
+    pub journal: Option<PathBuf>,
 }
@@ -0,0 +1,560 @@
+use anyhow::{Result, anyhow, bail};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use romv::{Case, Encoding, FileType, Form, LongVowels, OnConflict, Sanitize, Script, System};
+
+use crate::cli::Args;
+use crate::format::Format;
+use crate::xdg;
+
+/// Name of the project-local config file, looked up from the current
+/// directory upward.
+const PROJECT_FILE: &str = ".romv.toml";
+
+fn enum_value<T: ValueEnum, E: serde::de::Error>(s: &str) -> Result<T, E> {
+    T::from_str(s, false).map_err(|_| {
+        let expected: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        E::custom(format!(
+            "invalid value {s:?}, expected one of: {}",
+            expected.join(", ")
+        ))
+    })
+}
+
+fn de_enum<'de, D: Deserializer<'de>, T: ValueEnum>(d: D) -> Result<Option<T>, D::Error> {
+    Option::<String>::deserialize(d)?
+        .map(|s| enum_value(&s))
+        .transpose()
+}
+
+fn de_enums<'de, D: Deserializer<'de>, T: ValueEnum>(d: D) -> Result<Option<Vec<T>>, D::Error> {
+    Option::<Vec<String>>::deserialize(d)?
+        .map(|list| list.iter().map(|s| enum_value(s)).collect())
+        .transpose()
+}
+
+fn ser_enum<S: Serializer, T: ValueEnum>(value: &Option<T>, s: S) -> Result<S::Ok, S::Error> {
+    match value.as_ref().and_then(|v| v.to_possible_value()) {
+        Some(v) => s.serialize_str(v.get_name()),
+        None => s.serialize_none(),
+    }
+}
+
+fn ser_enums<S: Serializer, T: ValueEnum>(value: &Option<Vec<T>>, s: S) -> Result<S::Ok, S::Error> {
+    let names = value.as_ref().map(|list| {
+        list.iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect::<Vec<_>>()
+    });
+    names.serialize(s)
+}
+
+/// Defaults for command line options. Keys are the long option names.
+/// `-y`, `--plan`, `-0`, `--print0`, `--normalize-only` and the file list
+/// can only be given on the command line.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word_separator: Option<char>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case: Option<Case>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<System>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_vowels: Option<LongVowels>,
+    #[serde(deserialize_with = "de_enums", serialize_with = "ser_enums")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scripts: Option<Vec<Script>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multi_ext: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub romanize_ext: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lowercase_ext: Option<bool>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sanitize: Option<Sanitize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// Punctuation replacements, added to those given with `--map`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_ascii: Option<bool>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_form: Option<Form>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_encoding: Option<Encoding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dict: Option<PathBuf>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_conflict: Option<OnConflict>,
+    #[serde(deserialize_with = "de_enum", serialize_with = "ser_enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recursive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_path: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<String>>,
+    #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(
+        rename = "type",
+        deserialize_with = "de_enum",
+        serialize_with = "ser_enum"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<FileType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbose: Option<bool>,
+    /// Named sets of settings selected with `--profile`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+impl Settings {
+    pub fn parse(text: &str) -> Result<Self> {
+        let settings: Settings = toml::from_str(text)?;
+        if settings.profiles.values().any(|p| !p.profiles.is_empty()) {
+            bail!("profiles cannot contain other profiles");
+        }
+        let maps = std::iter::once(&settings.map).chain(settings.profiles.values().map(|p| &p.map));
+        for (from, to) in maps.flatten().flatten() {
+            let mapping = format!("{from}={to}");
+            romv::parse_mapping(&mapping)
+                .and_then(|(from, to)| romv::Punctuation::default().insert(from, &to))
+                .map_err(|e| anyhow!("map: {e}"))?;
+        }
+        Ok(settings)
+    }
+
+    /// Load a config file. A relative `dict` path is taken relative to
+    /// the directory of the file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read config {}: {e}", path.display()))?;
+        let mut settings = Self::parse(&text).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        settings.dict = settings.dict.map(|dict| base.join(dict));
+        for profile in settings.profiles.values_mut() {
+            profile.dict = profile.dict.take().map(|dict| base.join(dict));
+        }
+        Ok(settings)
+    }
+
+    /// Overwrite the settings that `other` sets.
+    fn merge(&mut self, other: &Settings) {
+        self.separator = other.separator.or(self.separator);
+        self.word_separator = other.word_separator.or(self.word_separator);
+        self.case = other.case.or(self.case);
+        self.system = other.system.or(self.system);
+        self.long_vowels = other.long_vowels.or(self.long_vowels);
+        self.scripts = other.scripts.clone().or(self.scripts.take());
+        self.multi_ext = other.multi_ext.clone().or(self.multi_ext.take());
+        self.romanize_ext = other.romanize_ext.or(self.romanize_ext);
+        self.lowercase_ext = other.lowercase_ext.or(self.lowercase_ext);
+        self.sanitize = other.sanitize.or(self.sanitize);
+        self.max_length = other.max_length.or(self.max_length);
+        if let Some(map) = &other.map {
+            self.map.get_or_insert_default().extend(map.clone());
+        }
+        self.strict_ascii = other.strict_ascii.or(self.strict_ascii);
+        self.output_form = other.output_form.or(self.output_form);
+        self.from_encoding = other.from_encoding.or(self.from_encoding);
+        self.dict = other.dict.clone().or(self.dict.take());
+        self.on_conflict = other.on_conflict.or(self.on_conflict);
+        self.format = other.format.or(self.format);
+        self.recursive = other.recursive.or(self.recursive);
+        self.max_depth = other.max_depth.or(self.max_depth);
+        self.full_path = other.full_path.or(self.full_path);
+        self.include = other.include.clone().or(self.include.take());
+        self.exclude = other.exclude.clone().or(self.exclude.take());
+        self.pattern = other.pattern.clone().or(self.pattern.take());
+        self.file_type = other.file_type.or(self.file_type);
+        self.interactive = other.interactive.or(self.interactive);
+        self.verbose = other.verbose.or(self.verbose);
+    }
+
+    /// Settings of the options in `args`, for `romv config show`.
+    pub fn from_args(args: &Args) -> Self {
+        Settings {
+            separator: Some(args.separator),
+            word_separator: args.word_separator,
+            case: Some(args.case),
+            system: Some(args.system),
+            long_vowels: args.long_vowels,
+            scripts: Some(args.scripts.clone()),
+            multi_ext: Some(args.multi_ext.clone()),
+            romanize_ext: Some(args.romanize_ext),
+            lowercase_ext: Some(args.lowercase_ext),
+            sanitize: args.sanitize,
+            max_length: args.max_length,
+            map: (!args.map.is_empty()).then(|| {
+                args.map
+                    .iter()
+                    .map(|(from, to)| (from.to_string(), to.clone()))
+                    .collect()
+            }),
+            strict_ascii: Some(args.strict_ascii),
+            output_form: Some(args.output_form),
+            from_encoding: args.from_encoding,
+            dict: args.dict.clone(),
+            on_conflict: Some(args.on_conflict),
+            format: Some(args.format),
+            recursive: Some(args.recursive),
+            max_depth: args.max_depth,
+            full_path: Some(args.full_path),
+            include: Some(args.include.clone()),
+            exclude: Some(args.exclude.clone()),
+            pattern: args.pattern.clone(),
+            file_type: args.file_type,
+            interactive: Some(args.interactive),
+            verbose: Some(args.verbose),
+            profiles: BTreeMap::new(),
+        }
+    }
+
+    /// Set every option of `args` that was not given on the command line,
+    /// with or without `--no-`.
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        // A flag is also set by its `--no-` counterpart
+        let unset_flag = |id: &str| unset(id) && unset(&format!("no_{id}"));
+
+        if let Some(v) = self.separator
+            && unset("separator")
+        {
+            args.separator = v;
+        }
+        if let Some(v) = self.word_separator
+            && unset("word_separator")
+        {
+            args.word_separator = Some(v);
+        }
+        if let Some(v) = self.case
+            && unset("case")
+        {
+            args.case = v;
+        }
+        if let Some(v) = self.system
+            && unset("system")
+        {
+            args.system = v;
+        }
+        if let Some(v) = self.long_vowels
+            && unset("long_vowels")
+        {
+            args.long_vowels = Some(v);
+        }
+        if let Some(v) = &self.scripts
+            && unset("scripts")
+        {
+            args.scripts = v.clone();
+        }
+        if let Some(v) = &self.multi_ext
+            && unset("multi_ext")
+        {
+            args.multi_ext = v.clone();
+        }
+        if let Some(v) = self.romanize_ext
+            && unset_flag("romanize_ext")
+        {
+            args.romanize_ext = v;
+        }
+        if let Some(v) = self.lowercase_ext
+            && unset_flag("lowercase_ext")
+        {
+            args.lowercase_ext = v;
+        }
+        if let Some(v) = self.sanitize
+            && unset("sanitize")
+        {
+            args.sanitize = Some(v);
+        }
+        if let Some(v) = self.max_length
+            && unset("max_length")
+        {
+            args.max_length = Some(v);
+        }
+        if let Some(map) = &self.map {
+            // Command line mappings come last and win
+            let mut mappings: Vec<(char, String)> = map
+                .iter()
+                .filter_map(|(from, to)| romv::parse_mapping(&format!("{from}={to}")).ok())
+                .collect();
+            mappings.append(&mut args.map);
+            args.map = mappings;
+        }
+        if let Some(v) = self.strict_ascii
+            && unset_flag("strict_ascii")
+        {
+            args.strict_ascii = v;
+        }
+        if let Some(v) = self.output_form
+            && unset("output_form")
+        {
+            args.output_form = v;
+        }
+        if let Some(v) = self.from_encoding
+            && unset("from_encoding")
+        {
+            args.from_encoding = Some(v);
+        }
+        if let Some(v) = &self.dict
+            && unset("dict")
+        {
+            args.dict = Some(v.clone());
+        }
+        if let Some(v) = self.on_conflict
+            && unset("on_conflict")
+        {
+            args.on_conflict = v;
+        }
+        if let Some(v) = self.format
+            && unset("format")
+        {
+            args.format = v;
+        }
+        if let Some(v) = self.recursive
+            && unset_flag("recursive")
+        {
+            args.recursive = v;
+        }
+        if let Some(v) = self.max_depth
+            && unset("max_depth")
+        {
+            args.max_depth = Some(v);
+        }
+        if let Some(v) = self.full_path
+            && unset_flag("full_path")
+        {
+            args.full_path = v;
+        }
+        if let Some(v) = &self.include
+            && unset("include")
+        {
+            args.include = v.clone();
+        }
+        if let Some(v) = &self.exclude
+            && unset("exclude")
+        {
+            args.exclude = v.clone();
+        }
+        if let Some(v) = &self.pattern
+            && unset("pattern")
+        {
+            args.pattern = Some(v.clone());
+        }
+        if let Some(v) = self.file_type
+            && unset("file_type")
+        {
+            args.file_type = Some(v);
+        }
+        if let Some(v) = self.interactive
+            && unset_flag("interactive")
+        {
+            args.interactive = v;
+        }
+        if let Some(v) = self.verbose
+            && unset_flag("verbose")
+        {
+            args.verbose = v;
+        }
+    }
+}
+
+/// User config file: `$XDG_CONFIG_HOME/romv/config.toml`.
+pub fn user_path() -> Option<PathBuf> {
+    xdg::config_home().map(|dir| dir.join("romv").join("config.toml"))
+}
+
+/// User dictionary: `$XDG_CONFIG_HOME/romv/dict.tsv`.
+pub fn dict_path() -> Option<PathBuf> {
+    xdg::config_home().map(|dir| dir.join("romv").join("dict.tsv"))
+}
+
+/// Nearest `.romv.toml` in `dir` or one of its ancestors.
+pub fn project_path(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(PROJECT_FILE))
+        .find(|p| p.is_file())
+}
+
+/// Effective settings and the files they were read from.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub settings: Settings,
+    pub files: Vec<PathBuf>,
+}
+
+/// Combine the user and project config files, the project file taking
+/// precedence. A `profile` is applied on top of both and must be
+/// defined in at least one of them.
+pub fn resolve(files: Vec<PathBuf>, profile: Option<&str>) -> Result<Config> {
+    let loaded = files
+        .iter()
+        .map(|path| Settings::load(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut settings = Settings::default();
+    for file in &loaded {
+        settings.merge(file);
+    }
+    if let Some(name) = profile {
+        let profiles: Vec<&Settings> = loaded.iter().filter_map(|f| f.profiles.get(name)).collect();
+        if profiles.is_empty() {
+            bail!("profile not found: {name}");
+        }
+        for p in profiles {
+            settings.merge(p);
+        }
+    }
+
+    Ok(Config { settings, files })
+}
+
+/// Load the config files that apply in the current directory.
+pub fn load(profile: Option<&str>) -> Result<Config> {
+    let cwd = std::env::current_dir()?;
+    let files = user_path()
+        .filter(|p| p.is_file())
+        .into_iter()
+        .chain(project_path(&cwd))
+        .collect();
+    resolve(files, profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, text: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn apply(settings: &str, argv: &[&str]) -> Args {
+        use clap::{CommandFactory, FromArgMatches};
+
+        let matches = Args::command()
+            .try_get_matches_from(std::iter::once("romv").chain(argv.iter().copied()))
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        Settings::parse(settings)
+            .unwrap()
+            .apply(&mut args, &matches);
+        args
+    }
+
+    #[test]
+    fn no_flags_override_config() {
+        assert!(apply("recursive = true", &[]).recursive);
+        assert!(!apply("recursive = true", &["--no-recursive"]).recursive);
+        assert!(apply("recursive = false", &["--no-recursive", "-r"]).recursive);
+        assert!(!apply("verbose = true\nstrict-ascii = true", &["--no-verbose"]).verbose);
+    }
+
+    #[test]
+    fn parse_enums_by_cli_name() {
+        let settings =
+            Settings::parse("case = \"kebab\"\non-conflict = \"overwrite-backup\"\n").unwrap();
+        assert_eq!(settings.case, Some(Case::Kebab));
+        assert_eq!(settings.on_conflict, Some(OnConflict::OverwriteBackup));
+        assert!(Settings::parse("case = \"shouting\"").is_err());
+        let settings = Settings::parse("scripts = [\"hangul\", \"latin\"]").unwrap();
+        assert_eq!(settings.scripts, Some(vec![Script::Hangul, Script::Latin]));
+        assert!(Settings::parse("scripts = [\"klingon\"]").is_err());
+        let settings = Settings::parse("type = \"d\"\nmatch = \"^IMG\"\n").unwrap();
+        assert_eq!(settings.file_type, Some(FileType::Dir));
+        assert_eq!(settings.pattern.as_deref(), Some("^IMG"));
+        assert!(Settings::parse("colour = true").is_err());
+    }
+
+    #[test]
+    fn project_overrides_user_and_profile_overrides_both() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write(
+            dir.path(),
+            "user.toml",
+            "separator = \"-\"\ncase = \"snake\"\n[profiles.photos]\nsystem = \"kunrei\"\n",
+        );
+        let project = write(
+            dir.path(),
+            "project.toml",
+            "case = \"kebab\"\n[profiles.photos]\ncase = \"pascal\"\n",
+        );
+
+        let config = resolve(vec![user.clone(), project.clone()], None).unwrap();
+        assert_eq!(config.settings.separator, Some('-'));
+        assert_eq!(config.settings.case, Some(Case::Kebab));
+        assert_eq!(config.settings.system, None);
+
+        let config = resolve(vec![user.clone(), project.clone()], Some("photos")).unwrap();
+        assert_eq!(config.settings.case, Some(Case::Pascal));
+        assert_eq!(config.settings.system, Some(System::Kunrei));
+
+        assert!(resolve(vec![user, project], Some("music")).is_err());
+    }
+
+    #[test]
+    fn maps_combine_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write(
+            dir.path(),
+            "user.toml",
+            "[map]\n\"【\" = \"(\"\n\"】\" = \")\"\n",
+        );
+        let project = write(dir.path(), "project.toml", "[map]\n\"】\" = \"\"\n");
+
+        let config = resolve(vec![user, project], None).unwrap();
+        let map = config.settings.map.unwrap();
+        assert_eq!(map["【"], "(");
+        assert_eq!(map["】"], "");
+        assert!(Settings::parse("[map]\n\"【】\" = \"[]\"\n").is_err());
+        assert!(Settings::parse("[map]\n\"→\" = \"/\"\n").is_err());
+    }
+
+    #[test]
+    fn dict_is_relative_to_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "config.toml", "dict = \"words.tsv\"\n");
+        let settings = Settings::load(&path).unwrap();
+        assert_eq!(settings.dict, Some(dir.path().join("words.tsv")));
+    }
+
+    #[test]
+    fn project_file_found_upward() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b");
+        std::fs::create_dir_all(&nested).unwrap();
+        let path = write(dir.path(), PROJECT_FILE, "");
+        assert_eq!(project_path(&nested), Some(path));
+    }
+}
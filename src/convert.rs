@@ -1,83 +1,622 @@
+use anyhow::{Result, bail};
+use std::ffi::OsStr;
 use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::case::{self, Case};
+use crate::dict::Dictionary;
+use crate::encoding::{self, Encoding};
+use crate::normalize::{self, Form};
+use crate::punct::Punctuation;
+use crate::romaji::{self, LongVowels, System};
+use crate::sanitize::{self, Fix, Sanitize};
+use crate::segment;
+use crate::translit::{self, Script};
+
+/// Options controlling how a filename is converted.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Character to replace spaces in the original name with
+    pub separator: char,
+    /// Romanization system used for kana readings
+    pub system: System,
+    /// Spelling of long vowels; `None` uses the default of `system`
+    pub long_vowels: Option<LongVowels>,
+    /// Separator placed between Japanese words; `None` joins them
+    pub word_separator: Option<char>,
+    /// Case style of the converted stem
+    pub case: Case,
+    /// User readings consulted before kakasi
+    pub dictionary: Dictionary,
+    /// Normalization form of the converted name
+    pub output_form: Form,
+    /// Only normalize the name, without romanizing it
+    pub normalize_only: bool,
+    /// Non-Japanese scripts transliterated to ASCII
+    pub scripts: Vec<Script>,
+    /// Extensions of several parts kept together, without the leading dot
+    pub multi_extensions: Vec<String>,
+    /// Romanize Japanese in extensions too
+    pub romanize_extension: bool,
+    /// Lowercase extensions (`.JPG` → `.jpg`)
+    pub lowercase_extension: bool,
+    /// Restrict converted names to a portable character set
+    pub sanitize: Option<Sanitize>,
+    /// Longest converted name in bytes; `None` asks the filesystem
+    pub max_length: Option<usize>,
+    /// Replacements for punctuation and symbols
+    pub punctuation: Punctuation,
+}
+
+/// Extensions of several parts that are split off as a whole:
+/// `データ.tar.gz` has the stem `データ`.
+pub const MULTI_EXTENSIONS: &[&str] = &["tar.gz", "tar.bz2", "tar.xz", "tar.zst", "d.ts"];
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            separator: '_',
+            system: System::default(),
+            long_vowels: None,
+            word_separator: None,
+            case: Case::default(),
+            dictionary: Dictionary::default(),
+            output_form: Form::default(),
+            normalize_only: false,
+            scripts: Vec::new(),
+            multi_extensions: MULTI_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            romanize_extension: false,
+            lowercase_extension: false,
+            sanitize: None,
+            max_length: None,
+            punctuation: Punctuation::default(),
+        }
+    }
+}
+
+/// Converts filenames from Japanese to romaji.
+///
+/// ```
+/// use romv::Converter;
+///
+/// let converter = Converter::default();
+/// assert_eq!(converter.convert("テスト.txt"), "tesuto.txt");
+/// assert_eq!(converter.convert(".設定.conf"), ".settei.conf");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Converter {
+    opts: Options,
+    from_encoding: Option<Encoding>,
+}
+
+/// Builder for [`Converter`], created by [`Converter::builder`].
+///
+/// ```
+/// use romv::{Case, Converter, Dictionary, LongVowels, System};
+///
+/// let mut dictionary = Dictionary::default();
+/// dictionary.insert("東京", "tokyo");
+/// let converter = Converter::builder()
+///     .system(System::Kunrei)
+///     .long_vowels(LongVowels::Macron)
+///     .word_separator('_')
+///     .dictionary(dictionary)
+///     .build()
+///     .unwrap();
+/// assert_eq!(converter.convert("東京の写真.jpg"), "tokyo_no_syasin.jpg");
+///
+/// let converter = Converter::builder().case(Case::Pascal).build().unwrap();
+/// assert_eq!(converter.convert("会議資料.pdf"), "KaigiShiryou.pdf");
+///
+/// assert!(Converter::builder().separator('/').build().is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ConverterBuilder {
+    opts: Options,
+    from_encoding: Option<Encoding>,
+}
+
+fn validate_separator(separator: char) -> Result<()> {
+    match separator {
+        '/' | '\0' => bail!("invalid separator: cannot use '/' or null character"),
+        '.' => bail!("invalid separator: '.' would break file extension parsing"),
+        _ => Ok(()),
+    }
+}
+
+impl ConverterBuilder {
+    /// Character to replace spaces in the original name with (default `_`)
+    pub fn separator(mut self, separator: char) -> Self {
+        self.opts.separator = separator;
+        self
+    }
+
+    /// Character to put between Japanese words (default: none)
+    pub fn word_separator(mut self, separator: char) -> Self {
+        self.opts.word_separator = Some(separator);
+        self
+    }
+
+    /// Case style of the converted stem (default: preserve)
+    pub fn case(mut self, case: Case) -> Self {
+        self.opts.case = case;
+        self
+    }
+
+    /// Romanization system for kana readings (default: Hepburn)
+    pub fn system(mut self, system: System) -> Self {
+        self.opts.system = system;
+        self
+    }
+
+    /// Long vowel spelling (default: depends on the system)
+    pub fn long_vowels(mut self, long_vowels: LongVowels) -> Self {
+        self.opts.long_vowels = Some(long_vowels);
+        self
+    }
+
+    /// User readings consulted before kakasi
+    pub fn dictionary(mut self, dictionary: Dictionary) -> Self {
+        self.opts.dictionary = dictionary;
+        self
+    }
+
+    /// Also transliterate these non-Japanese scripts (default: none)
+    ///
+    /// ```
+    /// use romv::{Converter, Script};
+    ///
+    /// let converter = Converter::builder()
+    ///     .scripts([Script::Hangul, Script::Latin])
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(converter.convert("한국어_café.txt"), "hangugeo_cafe.txt");
+    /// ```
+    pub fn scripts(mut self, scripts: impl IntoIterator<Item = Script>) -> Self {
+        self.opts.scripts = scripts.into_iter().collect();
+        self
+    }
+
+    /// Add an extension of several parts to keep together, such as
+    /// `tar.lz` (see [`MULTI_EXTENSIONS`] for the defaults)
+    pub fn multi_extension(mut self, extension: impl Into<String>) -> Self {
+        let extension = extension.into();
+        self.opts
+            .multi_extensions
+            .push(extension.trim_start_matches('.').to_string());
+        self
+    }
+
+    /// Romanize Japanese in extensions as well as in the stem
+    ///
+    /// ```
+    /// use romv::Converter;
+    ///
+    /// let converter = Converter::builder()
+    ///     .romanize_extension(true)
+    ///     .lowercase_extension(true)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(converter.convert("資料.バックアップ"), "shiryou.bakkuappu");
+    /// assert_eq!(converter.convert("写真.JPG"), "shashin.jpg");
+    /// ```
+    pub fn romanize_extension(mut self, romanize: bool) -> Self {
+        self.opts.romanize_extension = romanize;
+        self
+    }
+
+    /// Lowercase extensions (`.JPG` → `.jpg`)
+    pub fn lowercase_extension(mut self, lowercase: bool) -> Self {
+        self.opts.lowercase_extension = lowercase;
+        self
+    }
+
+    /// Restrict converted names to the characters `mode` allows
+    /// (default: no restriction)
+    ///
+    /// ```
+    /// use romv::{Converter, Sanitize};
+    ///
+    /// let converter = Converter::builder().sanitize(Sanitize::Windows).build().unwrap();
+    /// assert_eq!(converter.convert("会議: 明日?.txt"), "kaigi_ashita.txt");
+    /// assert_eq!(converter.convert("CON.txt"), "CON_.txt");
+    /// ```
+    pub fn sanitize(mut self, mode: Sanitize) -> Self {
+        self.opts.sanitize = Some(mode);
+        self
+    }
+
+    /// Longest name in bytes that [`RenamePlan::build`] allows before
+    /// truncating (default: the limit of the target filesystem)
+    ///
+    /// [`RenamePlan::build`]: crate::RenamePlan::build
+    pub fn max_length(mut self, bytes: usize) -> Self {
+        self.opts.max_length = Some(bytes);
+        self
+    }
+
+    /// Replacements for punctuation and symbols (default:
+    /// [`DEFAULT_MAPPINGS`](crate::DEFAULT_MAPPINGS))
+    ///
+    /// ```
+    /// use romv::{Converter, Punctuation};
+    ///
+    /// let converter = Converter::default();
+    /// assert_eq!(converter.convert("【速報】ロミオ・ジュリエット。.txt"), "[sokuhou]romio_jurietto.txt");
+    ///
+    /// let mut punctuation = Punctuation::default();
+    /// punctuation.insert('・', "-").unwrap();
+    /// let converter = Converter::builder().punctuation(punctuation).build().unwrap();
+    /// assert_eq!(converter.convert("ロミオ・ジュリエット.txt"), "romio-jurietto.txt");
+    /// ```
+    pub fn punctuation(mut self, punctuation: Punctuation) -> Self {
+        self.opts.punctuation = punctuation;
+        self
+    }
+
+    /// Normalization form of converted names (default NFC)
+    pub fn output_form(mut self, form: Form) -> Self {
+        self.opts.output_form = form;
+        self
+    }
+
+    /// Only normalize names (NFD → NFC, or to the output form) instead
+    /// of romanizing them
+    pub fn normalize_only(mut self, normalize_only: bool) -> Self {
+        self.opts.normalize_only = normalize_only;
+        self
+    }
+
+    /// Decode names that are not valid UTF-8 from this encoding
+    /// (default: such names cannot be converted)
+    pub fn from_encoding(mut self, encoding: Encoding) -> Self {
+        self.from_encoding = Some(encoding);
+        self
+    }
+
+    /// Build the converter. Fails if a separator is `/`, `.` or NUL, or
+    /// if a multi-part extension is not of the form `a.b`.
+    pub fn build(self) -> Result<Converter> {
+        validate_separator(self.opts.separator)?;
+        if let Some(word_separator) = self.opts.word_separator {
+            validate_separator(word_separator)?;
+        }
+        for extension in &self.opts.multi_extensions {
+            if !extension.contains('.')
+                || extension.ends_with('.')
+                || extension.contains(['/', '\0'])
+            {
+                bail!("invalid multi-part extension: {extension:?}");
+            }
+        }
+        Ok(Converter {
+            opts: self.opts,
+            from_encoding: self.from_encoding,
+        })
+    }
+}
+
+impl Converter {
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder::default()
+    }
+
+    /// Convert one filename (not a path). Only the stem is romanized;
+    /// leading dots and the extension are kept as they are unless the
+    /// builder says otherwise.
+    pub fn convert(&self, name: &str) -> String {
+        convert_filename(name, &self.opts)
+    }
+
+    /// Like [`Converter::convert`], also returning the changes made by
+    /// [`ConverterBuilder::sanitize`].
+    pub fn convert_with_fixes(&self, name: &str) -> (String, Vec<Fix>) {
+        let mut fixes = Vec::new();
+        let converted = convert_and_sanitize(name, &self.opts, &mut fixes);
+        (converted, fixes)
+    }
+
+    /// Length limit set with [`ConverterBuilder::max_length`].
+    pub fn max_length(&self) -> Option<usize> {
+        self.opts.max_length
+    }
+
+    /// Shorten a converted name to at most `limit` bytes, cutting the
+    /// stem at a word boundary and keeping leading dots and the
+    /// extension. `None` if even a one-character stem does not fit.
+    ///
+    /// ```
+    /// use romv::Converter;
+    ///
+    /// let converter = Converter::default();
+    /// assert_eq!(converter.truncate("kaigi_shiryou_final.pdf", 20).as_deref(), Some("kaigi_shiryou.pdf"));
+    /// assert_eq!(converter.truncate("kaigi.pdf", 4), None);
+    /// ```
+    pub fn truncate(&self, name: &str, limit: usize) -> Option<String> {
+        truncate_name(name, limit, &self.opts)
+    }
+
+    /// Append `_n` to the stem of a converted name, keeping leading dots
+    /// and the extension (including multi-part ones) in place. The name is
+    /// first [truncated](Converter::truncate) if needed so that it fits in
+    /// `limit` bytes with the suffix; `None` if it cannot be.
+    ///
+    /// ```
+    /// use romv::Converter;
+    ///
+    /// let converter = Converter::builder().multi_extension("tar.lz").build().unwrap();
+    /// assert_eq!(converter.with_suffix("tesuto.txt", 1, 255).as_deref(), Some("tesuto_1.txt"));
+    /// assert_eq!(converter.with_suffix("furui.tar.lz", 1, 255).as_deref(), Some("furui_1.tar.lz"));
+    /// assert_eq!(converter.with_suffix("kaigi_shiryou.pdf", 1, 16).as_deref(), Some("kaigi_1.pdf"));
+    /// ```
+    pub fn with_suffix(&self, name: &str, n: usize, limit: usize) -> Option<String> {
+        with_suffix(name, n, limit, &self.opts)
+    }
+
+    /// Decode a filename as found on disk: UTF-8 names as they are, other
+    /// names from the encoding set with [`ConverterBuilder::from_encoding`].
+    ///
+    /// ```
+    /// use romv::{Converter, Encoding};
+    /// use std::ffi::OsStr;
+    /// use std::os::unix::ffi::OsStrExt;
+    ///
+    /// let sjis = OsStr::from_bytes(b"\x83\x65\x83\x58\x83\x67.txt");
+    /// let converter = Converter::builder().from_encoding(Encoding::Sjis).build().unwrap();
+    /// let name = converter.decode(sjis).unwrap();
+    /// assert_eq!(name, "テスト.txt");
+    /// assert_eq!(converter.convert(&name), "tesuto.txt");
+    /// assert_eq!(Converter::default().decode(sjis), None);
+    /// ```
+    pub fn decode(&self, name: &OsStr) -> Option<String> {
+        encoding::decode(name, self.from_encoding)
+    }
+}
 
 fn is_japanese(c: char) -> bool {
     matches!(c,
+        '\u{3005}'..='\u{3006}' | // Iteration mark 々, closing mark 〆
+        '\u{303B}' |               // Vertical iteration mark 〻
         '\u{3040}'..='\u{309F}' | // Hiragana
-        '\u{30A0}'..='\u{30FF}' | // Katakana
+        '\u{30A0}'..='\u{30FF}' | // Katakana (including ヶ)
+        '\u{31F0}'..='\u{31FF}' | // Katakana Phonetic Extensions
         '\u{4E00}'..='\u{9FFF}' | // CJK Unified Ideographs
         '\u{3400}'..='\u{4DBF}' | // CJK Extension A
         '\u{F900}'..='\u{FAFF}' | // CJK Compatibility Ideographs
-        '\u{FF66}'..='\u{FF9F}'   // Halfwidth Katakana
+        '\u{FF66}'..='\u{FF9F}' | // Halfwidth Katakana
+        '\u{20000}'..='\u{2A6DF}' | // CJK Extension B
+        '\u{2A700}'..='\u{2EBEF}' | // CJK Extensions C to F
+        '\u{2F800}'..='\u{2FA1F}' | // CJK Compatibility Ideographs Supplement
+        '\u{30000}'..='\u{3134F}'   // CJK Extension G
     )
 }
 
+/// Kanji outside kakasi's dictionary that have a common equivalent,
+/// mostly JIS X 0213 variants found in names.
+fn kanji_variant(c: char) -> Option<char> {
+    let v = match c {
+        '𠮷' => '吉',
+        '𡈽' => '土',
+        '𠮟' => '叱',
+        '𥔎' => '崎',
+        '𣘺' => '橋',
+        '𦚰' => '脇',
+        '𠀋' => '丈',
+        '𤋮' => '煕',
+        _ => return None,
+    };
+    Some(v)
+}
+
+/// The kana a kana iteration mark repeats: `prev` without its voicing
+/// mark, voiced again for `ゞ` and `ヾ` (`いすゞ` → `いすず`).
+fn repeat_kana(prev: char, voiced: bool) -> char {
+    let base = prev.nfd().next().unwrap_or(prev);
+    if !voiced {
+        return base;
+    }
+    let mut composed = [base, '\u{3099}'].into_iter().nfc();
+    match (composed.next(), composed.next()) {
+        (Some(c), None) => c,
+        _ => base,
+    }
+}
+
+/// Rewrite characters kakasi has no reading for into ones it reads:
+/// `〻` as `々`, `〆` as `締` before a kanji (`〆切` → `しめきり`) and
+/// as `しめ` otherwise, the kana iteration marks `ゝゞヽヾ` as the kana
+/// before them, and the variants in [`kanji_variant`].
+fn fold_marks(japanese: &str) -> String {
+    let chars: Vec<char> = japanese.chars().collect();
+    let mut out = String::with_capacity(japanese.len());
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '〻' => out.push('々'),
+            'ゝ' | 'ゞ' | 'ヽ' | 'ヾ' => match out.chars().next_back() {
+                Some(prev) => out.push(repeat_kana(prev, matches!(c, 'ゞ' | 'ヾ'))),
+                None => out.push(c),
+            },
+            '〆' if chars
+                .get(i + 1)
+                .is_some_and(|&n| ('\u{4E00}'..='\u{9FFF}').contains(&n)) =>
+            {
+                out.push('締')
+            }
+            '〆' => out.push_str("しめ"),
+            _ => out.push(kanji_variant(c).unwrap_or(c)),
+        }
+    }
+    out
+}
+
 /// Normalize fullwidth ASCII characters (U+FF01..U+FF5E) to their
 /// halfwidth equivalents (U+0021..U+007E). Fullwidth space (U+3000)
-/// is converted to a regular space.
+/// is converted to a regular space. Other punctuation is mapped by
+/// [`Punctuation`] beforehand.
 fn normalize_fullwidth(c: char) -> char {
     match c {
         '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFF01 + 0x21).unwrap_or(c),
         '\u{3000}' => ' ',
-        '\u{301C}' => '~',  // 波ダッシュ 〜
-        '\u{3001}' => ',',  // 読点 、
-        '\u{3002}' => '.',  // 句点 。
         _ => c,
     }
 }
 
+/// Part of a Japanese run: a user dictionary hit, or text left to kakasi
+/// (with [`fold_marks`] applied).
+enum Piece<'a> {
+    Known(&'a str),
+    Unknown(String),
+}
+
+/// Split a Japanese run at user dictionary entries, preferring the
+/// longest entry at each position.
+fn dictionary_pieces<'a>(japanese: &str, dictionary: &'a Dictionary) -> Vec<Piece<'a>> {
+    let mut pieces = Vec::new();
+    let mut unknown = String::new();
+    let mut rest = japanese;
+
+    while let Some(c) = rest.chars().next() {
+        match dictionary.longest_match(rest) {
+            Some((len, romaji)) => {
+                if !unknown.is_empty() {
+                    pieces.push(Piece::Unknown(fold_marks(&std::mem::take(&mut unknown))));
+                }
+                pieces.push(Piece::Known(romaji));
+                rest = &rest[len..];
+            }
+            None => {
+                unknown.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !unknown.is_empty() {
+        pieces.push(Piece::Unknown(fold_marks(&unknown)));
+    }
+
+    pieces
+}
+
+/// Romanize a run of Japanese text word by word. A user dictionary hit
+/// is always a word of its own.
+fn romanize_words(japanese: &str, opts: &Options, long_vowels: LongVowels) -> Vec<String> {
+    let mut words = Vec::new();
+    for piece in dictionary_pieces(japanese, &opts.dictionary) {
+        match piece {
+            Piece::Known(romaji) => words.push(romaji.to_string()),
+            Piece::Unknown(text) => words.extend(
+                segment::words(&text)
+                    .iter()
+                    .map(|w| {
+                        romaji::romanize_with_okurigana(
+                            &w.kana,
+                            &w.okurigana,
+                            opts.system,
+                            long_vowels,
+                        )
+                    })
+                    .filter(|w| !w.is_empty()),
+            ),
+        }
+    }
+    words
+}
+
+/// Split `s` into maximal runs of Japanese and non-Japanese characters,
+/// tagged with whether the run is Japanese.
+fn runs(s: &str) -> Vec<(bool, String)> {
+    let mut runs: Vec<(bool, String)> = Vec::new();
+    for c in s.chars() {
+        let japanese = is_japanese(c);
+        match runs.last_mut() {
+            Some((j, run)) if *j == japanese => run.push(c),
+            _ => runs.push((japanese, c.to_string())),
+        }
+    }
+    runs
+}
+
 /// Convert only Japanese segments of a string via kakasi, preserving
 /// ASCII and other non-Japanese characters as-is. This prevents kakasi
 /// from mangling digits adjacent to kanji (e.g. `第10回` → `dai10kai`
 /// instead of the broken `daiichi 0 kai`).
 ///
-/// Fullwidth ASCII characters are normalized to halfwidth before processing.
-fn convert_segments(s: &str, separator: char) -> String {
+/// Punctuation is mapped with `opts.punctuation`, fullwidth ASCII characters
+/// are normalized to halfwidth before processing, and the scripts selected
+/// in `opts.scripts` are transliterated.
+/// Entries of the user dictionary in `opts` take priority over kakasi.
+/// Readings are spelled in the romanization system selected in `opts`,
+/// with words joined by `opts.word_separator` if set. Unless `opts.case`
+/// is [`Case::Preserve`], the whole stem is split into words and rendered
+/// in that case style instead.
+fn convert_segments(s: &str, opts: &Options) -> String {
+    let s = &opts.punctuation.apply(s);
+    let s = &translit::transliterate(s, &opts.scripts);
+    let long_vowels = opts
+        .long_vowels
+        .unwrap_or_else(|| opts.system.default_long_vowels());
+
+    if opts.case != Case::Preserve {
+        let mut words = Vec::new();
+        for (japanese, run) in runs(s) {
+            if japanese {
+                words.extend(romanize_words(&run, opts, long_vowels));
+            } else {
+                let normalized: String = run.chars().map(normalize_fullwidth).collect();
+                words.extend(case::split_words(&normalized));
+            }
+        }
+        return case::render(&words, opts.case, opts.separator);
+    }
+
     let mut result = String::new();
-    let mut chars = s.chars().peekable();
-
-    while let Some(&c) = chars.peek() {
-        if is_japanese(c) {
-            // Collect a run of Japanese characters
-            let mut japanese = String::new();
-            while let Some(&c) = chars.peek() {
-                if is_japanese(c) {
-                    japanese.push(c);
-                    chars.next();
+    for (japanese, run) in runs(s) {
+        if !japanese {
+            for c in run.chars() {
+                let normalized = normalize_fullwidth(c);
+                if normalized == ' ' {
+                    result.push(opts.separator);
                 } else {
-                    break;
+                    result.push(normalized);
                 }
             }
-            let converted = kakasi::convert(&japanese);
-            let romaji = converted.romaji.to_lowercase();
-            let romaji = romaji.replace(' ', "");
-            result.push_str(&romaji);
-        } else {
-            let normalized = normalize_fullwidth(c);
-            if normalized == ' ' {
-                result.push(separator);
-            } else {
-                result.push(normalized);
+            continue;
+        }
+        match opts.word_separator {
+            Some(word_separator) => {
+                let words = romanize_words(&run, opts, long_vowels);
+                result.push_str(&words.join(word_separator.encode_utf8(&mut [0; 4])));
+            }
+            None => {
+                for piece in dictionary_pieces(&run, &opts.dictionary) {
+                    match piece {
+                        Piece::Known(romaji) => result.push_str(romaji),
+                        Piece::Unknown(text) => {
+                            let reading = segment::reading(&text);
+                            result.push_str(&romaji::romanize_with_okurigana(
+                                &reading.kana,
+                                &reading.okurigana,
+                                opts.system,
+                                long_vowels,
+                            ));
+                        }
+                    }
+                }
             }
-            chars.next();
         }
     }
 
     result
 }
 
-/// Convert a filename from Japanese to romaji.
-///
-/// Converts only the stem (filename without the final extension), treating
-/// it as the user-intentional naming part. Structural parts of the filename
-/// are preserved as-is:
-/// - Leading dots (hidden file marker): `.設定.conf` → `.settei.conf`
-/// - Extension (file type identifier): `テスト.txt` → `tesuto.txt`
-/// - Spaces in the original name are replaced with `separator`
-pub fn convert_filename(name: &str, separator: char) -> String {
-    if name.is_empty() {
-        return String::new();
-    }
-
+/// Split a filename into leading dots (hidden file marker), stem and
+/// extension. The extension is one of `multi` (compared ignoring ASCII case)
+/// if the name ends with it, otherwise the final one. Returns `None` for
+/// names consisting only of dots.
+fn split_name<'a, S: AsRef<str>>(
+    name: &'a str,
+    multi: &[S],
+) -> Option<(&'a str, &'a str, Option<&'a str>)> {
     // Preserve leading dot(s) for hidden files
     let (prefix, rest) = if name.starts_with('.') {
         let dot_end = name.find(|c: char| c != '.').unwrap_or(name.len());
@@ -87,20 +626,165 @@ pub fn convert_filename(name: &str, separator: char) -> String {
     };
 
     if rest.is_empty() {
-        return name.to_string();
+        return None;
+    }
+
+    for extension in multi {
+        let extension = extension.as_ref();
+        let Some(dot) = rest.len().checked_sub(extension.len() + 1) else {
+            continue;
+        };
+        if dot > 0
+            && rest.is_char_boundary(dot)
+            && rest[dot..].starts_with('.')
+            && rest[dot + 1..].eq_ignore_ascii_case(extension)
+        {
+            return Some((prefix, &rest[..dot], Some(&rest[dot + 1..])));
+        }
     }
 
     // Split extension from stem (use the rest after leading dots)
     let path = Path::new(rest);
     let ext = path.extension().and_then(|e| e.to_str());
     let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(rest);
+    Some((prefix, stem, ext))
+}
+
+/// Convert an extension: romanized with the case and word separator of
+/// the stem left out if `opts.romanize_extension`, then lowercased if
+/// `opts.lowercase_extension`.
+fn convert_extension(ext: &str, opts: &Options) -> String {
+    let ext = if opts.romanize_extension && !ext.is_ascii() {
+        let opts = Options {
+            case: Case::Preserve,
+            word_separator: None,
+            ..opts.clone()
+        };
+        convert_segments(ext, &opts)
+    } else {
+        ext.to_string()
+    };
+    if opts.lowercase_extension {
+        ext.to_lowercase()
+    } else {
+        ext
+    }
+}
+
+/// Convert a filename from Japanese to romaji.
+///
+/// Converts only the stem (filename without the final extension), treating
+/// it as the user-intentional naming part. Structural parts of the filename
+/// are preserved as-is:
+/// - Leading dots (hidden file marker): `.設定.conf` → `.settei.conf`
+/// - Extension (file type identifier): `テスト.txt` → `tesuto.txt`, unless
+///   `opts.romanize_extension` or `opts.lowercase_extension` is set.
+///   Extensions in `opts.multi_extensions` are kept whole:
+///   `データ.tar.gz` → `deeta.tar.gz`
+/// - Spaces in the original name are replaced with `opts.separator`
+///
+/// The name is normalized to NFC first, so decomposed names from macOS
+/// (`か` + U+3099) convert like composed ones. The result is sanitized
+/// if `opts.sanitize` is set (leading dots are kept) and normalized to
+/// `opts.output_form`.
+pub fn convert_filename(name: &str, opts: &Options) -> String {
+    convert_and_sanitize(name, opts, &mut Vec::new())
+}
+
+fn convert_and_sanitize(name: &str, opts: &Options, fixes: &mut Vec<Fix>) -> String {
+    if name.is_empty() {
+        return String::new();
+    }
+
+    let name = normalize::normalize(name, Form::Nfc);
+    let converted = if opts.normalize_only {
+        name
+    } else {
+        match split_name(&name, &opts.multi_extensions) {
+            Some((prefix, stem, Some(ext))) => format!(
+                "{prefix}{}.{}",
+                convert_segments(stem, opts),
+                convert_extension(ext, opts)
+            ),
+            Some((prefix, stem, None)) => format!("{prefix}{}", convert_segments(stem, opts)),
+            None => name.clone(),
+        }
+    };
+    let converted = match opts.sanitize {
+        Some(mode) => {
+            let dots = converted.len() - converted.trim_start_matches('.').len();
+            let (prefix, rest) = converted.split_at(dots);
+            if rest.is_empty() {
+                converted
+            } else {
+                let separators: Vec<char> = opts.word_separator.into_iter().collect();
+                let rest = sanitize::sanitize(rest, mode, opts.separator, &separators, fixes);
+                format!("{prefix}{rest}")
+            }
+        }
+        None => converted,
+    };
 
-    let converted = convert_segments(stem, separator);
+    normalize::normalize(&converted, opts.output_form)
+}
 
-    match ext {
-        Some(e) => format!("{prefix}{converted}.{e}"),
-        None => format!("{prefix}{converted}"),
+/// See [`Converter::truncate`]. A word ends before a separator, `_`,
+/// `-`, space or dot, or before a capital letter following a lowercase
+/// one or a digit (`KaigiShiryou` → `Kaigi`).
+fn truncate_name(name: &str, limit: usize, opts: &Options) -> Option<String> {
+    if name.len() <= limit {
+        return Some(name.to_string());
     }
+    let (prefix, stem, ext) = split_name(name, &opts.multi_extensions)?;
+    let tail = ext.map_or(0, |ext| ext.len() + 1);
+    let budget = limit.checked_sub(prefix.len() + tail)?;
+
+    let mut cut = budget.min(stem.len());
+    while !stem.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    if cut == 0 {
+        return None;
+    }
+
+    let is_separator = |c: char| {
+        c == opts.separator || Some(c) == opts.word_separator || matches!(c, '_' | '-' | ' ' | '.')
+    };
+    let mut word_end = None;
+    let mut prev: Option<char> = None;
+    for (i, c) in stem.char_indices() {
+        if i > cut {
+            break;
+        }
+        let hump = c.is_uppercase() && prev.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit());
+        if i > 0 && (is_separator(c) || hump) {
+            word_end = Some(i);
+        }
+        prev = Some(c);
+    }
+    let head = match word_end.map(|end| stem[..end].trim_end_matches(is_separator)) {
+        Some(head) if !head.is_empty() => head,
+        _ => &stem[..cut],
+    };
+
+    Some(match ext {
+        Some(ext) => format!("{prefix}{head}.{ext}"),
+        None => format!("{prefix}{head}"),
+    })
+}
+
+/// Append `_n` to the stem of a filename, keeping leading dots and the
+/// extension in place: `tesuto.txt` → `tesuto_1.txt`, `deeta.tar.gz` →
+/// `deeta_1.tar.gz`. A name too long for `limit` with the suffix is
+/// truncated first, as by [`truncate_name`].
+fn with_suffix(name: &str, n: usize, limit: usize, opts: &Options) -> Option<String> {
+    let suffix = format!("_{n}");
+    let name = truncate_name(name, limit.checked_sub(suffix.len())?, opts)?;
+    Some(match split_name(&name, &opts.multi_extensions) {
+        Some((prefix, stem, Some(ext))) => format!("{prefix}{stem}{suffix}.{ext}"),
+        Some((prefix, stem, None)) => format!("{prefix}{stem}{suffix}"),
+        None => format!("{name}{suffix}"),
+    })
 }
 
 #[cfg(test)]
@@ -109,40 +793,52 @@ mod tests {
 
     #[test]
     fn ascii_unchanged() {
-        assert_eq!(convert_filename("hello.txt", '_'), "hello.txt");
+        assert_eq!(
+            convert_filename("hello.txt", &Options::default()),
+            "hello.txt"
+        );
     }
 
     #[test]
     fn hiragana() {
-        let result = convert_filename("にほんご.txt", '_');
+        let result = convert_filename("にほんご.txt", &Options::default());
         assert_eq!(result, "nihongo.txt");
     }
 
     #[test]
     fn katakana() {
-        let result = convert_filename("テスト.txt", '_');
+        let result = convert_filename("テスト.txt", &Options::default());
         assert_eq!(result, "tesuto.txt");
     }
 
     #[test]
     fn kanji() {
-        assert_eq!(convert_filename("日本語.md", '_'), "nihongo.md");
+        assert_eq!(
+            convert_filename("日本語.md", &Options::default()),
+            "nihongo.md"
+        );
     }
 
     #[test]
     fn hidden_file() {
-        assert_eq!(convert_filename(".にほんご", '_'), ".nihongo");
+        assert_eq!(
+            convert_filename(".にほんご", &Options::default()),
+            ".nihongo"
+        );
     }
 
     #[test]
     fn hidden_file_with_ext() {
-        assert_eq!(convert_filename(".テスト.conf", '_'), ".tesuto.conf");
+        assert_eq!(
+            convert_filename(".テスト.conf", &Options::default()),
+            ".tesuto.conf"
+        );
     }
 
     #[test]
     fn space_replacement() {
         assert_eq!(
-            convert_filename("新しい ファイル.txt", '_'),
+            convert_filename("新しい ファイル.txt", &Options::default()),
             "atarashii_fairu.txt"
         );
     }
@@ -150,65 +846,706 @@ mod tests {
     #[test]
     fn custom_separator() {
         assert_eq!(
-            convert_filename("新しい ファイル.txt", '-'),
+            convert_filename(
+                "新しい ファイル.txt",
+                &Options {
+                    separator: '-',
+                    ..Options::default()
+                }
+            ),
             "atarashii-fairu.txt"
         );
     }
 
     #[test]
     fn empty_string() {
-        assert_eq!(convert_filename("", '_'), "");
+        assert_eq!(convert_filename("", &Options::default()), "");
     }
 
     #[test]
     fn dots_only() {
-        assert_eq!(convert_filename(".", '_'), ".");
-        assert_eq!(convert_filename("..", '_'), "..");
+        assert_eq!(convert_filename(".", &Options::default()), ".");
+        assert_eq!(convert_filename("..", &Options::default()), "..");
     }
 
     #[test]
     fn no_extension() {
-        let result = convert_filename("テスト", '_');
+        let result = convert_filename("テスト", &Options::default());
         assert_eq!(result, "tesuto");
     }
 
     #[test]
     fn digits_preserved_adjacent_to_kanji() {
-        assert_eq!(convert_filename("第10回.mp4", '_'), "dai10kai.mp4");
+        assert_eq!(
+            convert_filename("第10回.mp4", &Options::default()),
+            "dai10kai.mp4"
+        );
     }
 
     #[test]
     fn mixed_japanese_ascii_digits() {
-        assert_eq!(convert_filename("報告書_v2.pdf", '_'), "houkokusho_v2.pdf");
+        assert_eq!(
+            convert_filename("報告書_v2.pdf", &Options::default()),
+            "houkokusho_v2.pdf"
+        );
     }
 
     #[test]
     fn fullwidth_parentheses() {
-        assert_eq!(convert_filename("テスト（1）.txt", '_'), "tesuto(1).txt");
+        assert_eq!(
+            convert_filename("テスト（1）.txt", &Options::default()),
+            "tesuto(1).txt"
+        );
     }
 
     #[test]
     fn fullwidth_digits() {
-        assert_eq!(convert_filename("テスト１２３.txt", '_'), "tesuto123.txt");
+        assert_eq!(
+            convert_filename("テスト１２３.txt", &Options::default()),
+            "tesuto123.txt"
+        );
     }
 
     #[test]
     fn fullwidth_space() {
-        assert_eq!(convert_filename("テスト\u{3000}1.txt", '_'), "tesuto_1.txt");
+        assert_eq!(
+            convert_filename("テスト\u{3000}1.txt", &Options::default()),
+            "tesuto_1.txt"
+        );
     }
 
     #[test]
     fn wave_dash() {
-        assert_eq!(convert_filename("テスト〜1.txt", '_'), "tesuto~1.txt");
+        assert_eq!(
+            convert_filename("テスト〜1.txt", &Options::default()),
+            "tesuto~1.txt"
+        );
     }
 
     #[test]
     fn ideographic_comma() {
-        assert_eq!(convert_filename("テスト、1.txt", '_'), "tesuto,1.txt");
+        assert_eq!(
+            convert_filename("テスト、1.txt", &Options::default()),
+            "tesuto,1.txt"
+        );
+    }
+
+    #[test]
+    fn ideographic_period_adds_no_extension() {
+        assert_eq!(
+            convert_filename("テスト。1.txt", &Options::default()),
+            "tesuto1.txt"
+        );
+        assert_eq!(
+            convert_filename("完了。.txt", &Options::default()),
+            "kanryou.txt"
+        );
+    }
+
+    #[test]
+    fn every_default_punctuation_mapping() {
+        let expected = [
+            ('〜', "tesuto~1.txt"),
+            ('、', "tesuto,1.txt"),
+            ('。', "tesuto1.txt"),
+            ('・', "tesuto_1.txt"),
+            ('【', "tesuto[1.txt"),
+            ('】', "tesuto]1.txt"),
+            ('〔', "tesuto[1.txt"),
+            ('〕', "tesuto]1.txt"),
+            ('〖', "tesuto[1.txt"),
+            ('〗', "tesuto]1.txt"),
+            ('「', "tesuto[1.txt"),
+            ('」', "tesuto]1.txt"),
+            ('『', "tesuto[1.txt"),
+            ('』', "tesuto]1.txt"),
+            ('《', "tesuto(1.txt"),
+            ('》', "tesuto)1.txt"),
+            ('〈', "tesuto(1.txt"),
+            ('〉', "tesuto)1.txt"),
+            ('※', "tesuto1.txt"),
+            ('♪', "tesuto1.txt"),
+            ('★', "tesuto1.txt"),
+            ('☆', "tesuto1.txt"),
+            ('→', "tesuto-1.txt"),
+            ('←', "tesuto-1.txt"),
+            ('…', "tesuto1.txt"),
+            ('‥', "tesuto1.txt"),
+            ('‐', "tesuto-1.txt"),
+            ('–', "tesuto-1.txt"),
+            ('—', "tesuto-1.txt"),
+            ('―', "tesuto-1.txt"),
+            ('“', "tesuto1.txt"),
+            ('”', "tesuto1.txt"),
+            ('‘', "tesuto1.txt"),
+            ('’', "tesuto1.txt"),
+            ('×', "tesutox1.txt"),
+        ];
+        assert_eq!(expected.len(), crate::punct::DEFAULT_MAPPINGS.len());
+        for (c, name) in expected {
+            assert_eq!(
+                convert_filename(&format!("テスト{c}1.txt"), &Options::default()),
+                name,
+                "{c}"
+            );
+        }
+    }
+
+    #[test]
+    fn nakaguro_between_words() {
+        assert_eq!(
+            convert_filename(
+                "ロミオ・ジュリエット.txt",
+                &Options {
+                    case: Case::Kebab,
+                    ..Options::default()
+                }
+            ),
+            "romio-jurietto.txt"
+        );
+        assert_eq!(
+            convert_filename("【速報】会議.txt", &Options::default()),
+            "[sokuhou]kaigi.txt"
+        );
+    }
+
+    #[test]
+    fn hepburn_system() {
+        let opts = Options {
+            system: System::Hepburn,
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("写真.jpg", &opts), "shashin.jpg");
+        assert_eq!(convert_filename("地図.png", &opts), "chizu.png");
+        assert_eq!(convert_filename("富士山.jpg", &opts), "fujisan.jpg");
+        assert_eq!(convert_filename("つづく.txt", &opts), "tsuzuku.txt");
+    }
+
+    #[test]
+    fn kunrei_system() {
+        let opts = Options {
+            system: System::Kunrei,
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("写真.jpg", &opts), "syasin.jpg");
+        assert_eq!(convert_filename("地図.png", &opts), "tizu.png");
+        assert_eq!(convert_filename("富士山.jpg", &opts), "huzisan.jpg");
+        assert_eq!(convert_filename("つづく.txt", &opts), "tuzuku.txt");
+        assert_eq!(convert_filename("ちぢみ.txt", &opts), "tizimi.txt");
+        assert_eq!(convert_filename("抹茶.txt", &opts), "mattya.txt");
+    }
+
+    #[test]
+    fn nihon_system() {
+        let opts = Options {
+            system: System::Nihon,
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("写真.jpg", &opts), "syasin.jpg");
+        assert_eq!(convert_filename("富士山.jpg", &opts), "huzisan.jpg");
+        assert_eq!(convert_filename("つづく.txt", &opts), "tuduku.txt");
+        assert_eq!(convert_filename("ちぢみ.txt", &opts), "tidimi.txt");
+        assert_eq!(convert_filename("ヲ.txt", &opts), "wo.txt");
+    }
+
+    #[test]
+    fn passport_system() {
+        let opts = Options {
+            system: System::Passport,
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("新橋.jpg", &opts), "shimbashi.jpg");
+        assert_eq!(convert_filename("東京.jpg", &opts), "tokyo.jpg");
+        assert_eq!(convert_filename("大阪.jpg", &opts), "osaka.jpg");
+        assert_eq!(convert_filename("婚姻.txt", &opts), "konin.txt");
+        assert_eq!(convert_filename("抹茶.txt", &opts), "matcha.txt");
+    }
+
+    #[test]
+    fn system_keeps_ascii_and_extension() {
+        let opts = Options {
+            system: System::Kunrei,
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("写真_v2.JPG", &opts), "syasin_v2.JPG");
     }
 
     #[test]
-    fn ideographic_period() {
-        assert_eq!(convert_filename("テスト。1.txt", '_'), "tesuto.1.txt");
+    fn long_vowels_ou() {
+        let opts = Options {
+            long_vowels: Some(LongVowels::Ou),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("東京.txt", &opts), "toukyou.txt");
+        assert_eq!(convert_filename("コーヒー.txt", &opts), "koohii.txt");
+    }
+
+    #[test]
+    fn long_vowels_oh() {
+        let opts = Options {
+            long_vowels: Some(LongVowels::Oh),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("東京.txt", &opts), "tohkyoh.txt");
+        assert_eq!(convert_filename("大阪.txt", &opts), "ohsaka.txt");
+        assert_eq!(convert_filename("空気.txt", &opts), "kuki.txt");
+    }
+
+    #[test]
+    fn long_vowels_dropped() {
+        let opts = Options {
+            long_vowels: Some(LongVowels::O),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("東京.txt", &opts), "tokyo.txt");
+        assert_eq!(convert_filename("報告書.pdf", &opts), "hokokusho.pdf");
+        assert_eq!(convert_filename("コーヒー.txt", &opts), "kohi.txt");
+    }
+
+    #[test]
+    fn long_vowels_macron() {
+        let opts = Options {
+            long_vowels: Some(LongVowels::Macron),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("東京.txt", &opts), "tōkyō.txt");
+        assert_eq!(convert_filename("空気.txt", &opts), "kūki.txt");
+        assert_eq!(convert_filename("コーヒー.txt", &opts), "kōhī.txt");
+        assert_eq!(convert_filename("スーパー.txt", &opts), "sūpā.txt");
+    }
+
+    #[test]
+    fn long_vowels_circumflex() {
+        let opts = Options {
+            long_vowels: Some(LongVowels::Circumflex),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("東京.txt", &opts), "tôkyô.txt");
+        assert_eq!(convert_filename("ケーキ.txt", &opts), "kêki.txt");
+    }
+
+    #[test]
+    fn long_vowels_keep_distinct_vowels() {
+        let opts = Options {
+            long_vowels: Some(LongVowels::Macron),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("新しい.txt", &opts), "atarashii.txt");
+        assert_eq!(convert_filename("先生.txt", &opts), "sensei.txt");
+    }
+
+    #[test]
+    fn long_vowels_skip_okurigana() {
+        let drop = Options {
+            long_vowels: Some(LongVowels::O),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("追う.txt", &drop), "ou.txt");
+        assert_eq!(convert_filename("思う.txt", &drop), "omou.txt");
+        let macron = Options {
+            long_vowels: Some(LongVowels::Macron),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("追う.txt", &macron), "ou.txt");
+        assert_eq!(convert_filename("思う.txt", &macron), "omou.txt");
+        assert_eq!(convert_filename("行こう.txt", &macron), "ikō.txt");
+        assert_eq!(
+            convert_filename(
+                "思う東京.txt",
+                &Options {
+                    word_separator: Some('_'),
+                    ..macron
+                }
+            ),
+            "omou_tōkyō.txt"
+        );
+    }
+
+    #[test]
+    fn long_vowels_override_system_default() {
+        let opts = Options {
+            system: System::Passport,
+            long_vowels: Some(LongVowels::Oh),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("大野.txt", &opts), "ohno.txt");
+    }
+
+    #[test]
+    fn words_joined_by_default() {
+        assert_eq!(
+            convert_filename("新しいファイル.txt", &Options::default()),
+            "atarashiifairu.txt"
+        );
+    }
+
+    #[test]
+    fn word_separator_underscore() {
+        assert_eq!(
+            convert_filename(
+                "新しいファイル.txt",
+                &Options {
+                    word_separator: Some('_'),
+                    ..Options::default()
+                }
+            ),
+            "atarashii_fairu.txt"
+        );
+        assert_eq!(
+            convert_filename(
+                "会議資料.pdf",
+                &Options {
+                    word_separator: Some('_'),
+                    ..Options::default()
+                }
+            ),
+            "kaigi_shiryou.pdf"
+        );
+    }
+
+    #[test]
+    fn word_separator_hyphen() {
+        assert_eq!(
+            convert_filename(
+                "新しいファイル.txt",
+                &Options {
+                    word_separator: Some('-'),
+                    ..Options::default()
+                }
+            ),
+            "atarashii-fairu.txt"
+        );
+    }
+
+    #[test]
+    fn word_separator_independent_of_space_separator() {
+        let opts = Options {
+            separator: '_',
+            word_separator: Some('-'),
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("新しいファイル 第2版.txt", &opts),
+            "atarashii-fairu_dai2han.txt"
+        );
+    }
+
+    #[test]
+    fn word_separator_not_added_around_ascii() {
+        assert_eq!(
+            convert_filename(
+                "報告書_v2.pdf",
+                &Options {
+                    word_separator: Some('-'),
+                    ..Options::default()
+                }
+            ),
+            "houkokusho_v2.pdf"
+        );
+    }
+
+    #[test]
+    fn case_snake() {
+        let opts = Options {
+            case: Case::Snake,
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("新しいファイル.txt", &opts),
+            "atarashii_fairu.txt"
+        );
+        assert_eq!(
+            convert_filename("報告書 Final.pdf", &opts),
+            "houkokusho_final.pdf"
+        );
+    }
+
+    #[test]
+    fn case_kebab() {
+        let opts = Options {
+            case: Case::Kebab,
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("会議資料_draftCopy.md", &opts),
+            "kaigi-shiryou-draft-copy.md"
+        );
+    }
+
+    #[test]
+    fn case_camel() {
+        let opts = Options {
+            case: Case::Camel,
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("新しいファイル.txt", &opts),
+            "atarashiiFairu.txt"
+        );
+        assert_eq!(convert_filename("MY 写真.png", &opts), "myShashin.png");
+    }
+
+    #[test]
+    fn case_pascal() {
+        let opts = Options {
+            case: Case::Pascal,
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("新しいファイル.txt", &opts),
+            "AtarashiiFairu.txt"
+        );
+    }
+
+    #[test]
+    fn case_title() {
+        let opts = Options {
+            case: Case::Title,
+            separator: ' ',
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("新しいファイル.txt", &opts),
+            "Atarashii Fairu.txt"
+        );
+    }
+
+    #[test]
+    fn case_preserve_keeps_ascii() {
+        assert_eq!(
+            convert_filename(
+                "README_写真.md",
+                &Options {
+                    case: Case::Preserve,
+                    ..Options::default()
+                }
+            ),
+            "README_shashin.md"
+        );
+    }
+
+    #[test]
+    fn case_keeps_hidden_dot_and_extension() {
+        assert_eq!(
+            convert_filename(
+                ".設定ファイル.CONF",
+                &Options {
+                    case: Case::Pascal,
+                    ..Options::default()
+                }
+            ),
+            ".SetteiFairu.CONF"
+        );
+    }
+
+    #[test]
+    fn dictionary_overrides_kakasi() {
+        let opts = Options {
+            dictionary: Dictionary::parse("東海林\tshoji\n").unwrap(),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("東海林.txt", &opts), "shoji.txt");
+        assert_eq!(convert_filename("東海林様.txt", &opts), "shojisama.txt");
+    }
+
+    #[test]
+    fn dictionary_prefers_longest_match() {
+        let opts = Options {
+            dictionary: Dictionary::parse("新\tshin\n新宿\tshinjuku\n").unwrap(),
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("新宿駅.jpg", &opts), "shinjukueki.jpg");
+    }
+
+    #[test]
+    fn dictionary_entry_is_a_word() {
+        let opts = Options {
+            word_separator: Some('_'),
+            dictionary: Dictionary::parse("東海林\tshoji\n").unwrap(),
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("東海林の写真.jpg", &opts),
+            "shoji_no_shashin.jpg"
+        );
+    }
+
+    #[test]
+    fn suffix_before_extension() {
+        let suffixed = |name, n| with_suffix(name, n, 255, &Options::default()).unwrap();
+        assert_eq!(suffixed("tesuto.txt", 1), "tesuto_1.txt");
+        assert_eq!(suffixed("tesuto", 2), "tesuto_2");
+        assert_eq!(suffixed("deeta.tar.gz", 1), "deeta_1.tar.gz");
+    }
+
+    #[test]
+    fn suffix_keeps_hidden_dot() {
+        let suffixed = |name, n| with_suffix(name, n, 255, &Options::default()).unwrap();
+        assert_eq!(suffixed(".tesuto.conf", 1), ".tesuto_1.conf");
+        assert_eq!(suffixed(".settei", 1), ".settei_1");
+    }
+
+    #[test]
+    fn suffix_fits_limit() {
+        let opts = Options::default();
+        assert_eq!(
+            with_suffix("kaigi_shiryou.pdf", 10, 17, &opts).as_deref(),
+            Some("kaigi_10.pdf")
+        );
+        assert_eq!(
+            with_suffix("tōkyō.txt", 1, 8, &opts).as_deref(),
+            Some("t_1.txt")
+        );
+        assert_eq!(with_suffix(".tesuto.conf", 1, 8, &opts), None);
+    }
+
+    #[test]
+    fn decomposed_input_is_composed_first() {
+        let opts = Options::default();
+        // が and パ written as base kana + combining (semi-)voiced mark
+        assert_eq!(
+            convert_filename("か\u{3099}いこく.txt", &opts),
+            "gaikoku.txt"
+        );
+        assert_eq!(convert_filename("ハ\u{309A}ン.txt", &opts), "pan.txt");
+    }
+
+    #[test]
+    fn normalize_only_keeps_japanese() {
+        let opts = Options {
+            normalize_only: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("か\u{3099}いこく.txt", &opts),
+            "がいこく.txt"
+        );
+    }
+
+    #[test]
+    fn output_form_nfd() {
+        let opts = Options {
+            long_vowels: Some(LongVowels::Macron),
+            output_form: Form::Nfd,
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("東京.txt", &opts),
+            "to\u{304}kyo\u{304}.txt"
+        );
+    }
+
+    #[test]
+    fn iteration_and_closing_marks() {
+        let opts = Options::default();
+        assert_eq!(convert_filename("佐々木.txt", &opts), "sasaki.txt");
+        assert_eq!(convert_filename("佐〻木.txt", &opts), "sasaki.txt");
+        assert_eq!(convert_filename("〆切.txt", &opts), "shimekiri.txt");
+        assert_eq!(convert_filename("〆.txt", &opts), "shime.txt");
+    }
+
+    #[test]
+    fn kana_iteration_marks() {
+        let opts = Options::default();
+        assert_eq!(convert_filename("いすゞ.txt", &opts), "isuzu.txt");
+        assert_eq!(convert_filename("こゝろ.txt", &opts), "kokoro.txt");
+        assert_eq!(convert_filename("バナヽ.txt", &opts), "banana.txt");
+        assert_eq!(convert_filename("ミスヾ.txt", &opts), "misuzu.txt");
+        assert_eq!(convert_filename("ぶゝ.txt", &opts), "bufu.txt");
+        assert_eq!(repeat_kana('ず', true), 'ず');
+        assert_eq!(repeat_kana('ま', true), 'ま');
+    }
+
+    #[test]
+    fn small_ke_counter() {
+        assert_eq!(
+            convert_filename(
+                "一ヶ月.txt",
+                &Options {
+                    word_separator: Some('_'),
+                    ..Options::default()
+                }
+            ),
+            "ikkagetsu.txt"
+        );
+    }
+
+    #[test]
+    fn extension_kanji_variants() {
+        let opts = Options::default();
+        assert_eq!(convert_filename("𠮷野家.txt", &opts), "yoshinoya.txt");
+        assert_eq!(convert_filename("𠮟る.txt", &opts), "shikaru.txt");
+    }
+
+    #[test]
+    fn ainu_small_katakana() {
+        assert_eq!(
+            convert_filename("イタㇰ.txt", &Options::default()),
+            "itak.txt"
+        );
+        assert_eq!(
+            convert_filename("ㇷ\u{309A}.txt", &Options::default()),
+            "p.txt"
+        );
+    }
+
+    #[test]
+    fn multi_part_extensions() {
+        let opts = Options {
+            case: Case::Kebab,
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("データ.tar.gz", &opts), "deeta.tar.gz");
+        assert_eq!(convert_filename("型.D.TS", &opts), "kata.D.TS");
+        let opts = Options {
+            multi_extensions: vec!["tar.lz".to_string()],
+            ..opts
+        };
+        assert_eq!(convert_filename("古い.tar.lz", &opts), "furui.tar.lz");
+        assert_eq!(convert_filename(".tar.gz", &opts), ".tar.gz");
+    }
+
+    #[test]
+    fn extension_romanized_and_lowercased() {
+        let opts = Options {
+            romanize_extension: true,
+            case: Case::Pascal,
+            ..Options::default()
+        };
+        assert_eq!(
+            convert_filename("資料.バックアップ", &opts),
+            "Shiryou.bakkuappu"
+        );
+        assert_eq!(
+            convert_filename("資料.バックアップ", &Options::default()),
+            "shiryou.バックアップ"
+        );
+        let opts = Options {
+            lowercase_extension: true,
+            ..Options::default()
+        };
+        assert_eq!(convert_filename("写真.JPG", &opts), "shashin.jpg");
+        assert_eq!(convert_filename("photo.JPG", &opts), "photo.jpg");
+        assert_eq!(convert_filename(".BASHRC", &opts), ".BASHRC");
+    }
+
+    #[test]
+    fn truncate_at_word_boundary() {
+        let opts = Options::default();
+        let truncate = |name, limit| truncate_name(name, limit, &opts);
+        assert_eq!(
+            truncate("kaigi_shiryou_final.pdf", 20).as_deref(),
+            Some("kaigi_shiryou.pdf")
+        );
+        assert_eq!(
+            truncate("KaigiShiryouFinal.tar.gz", 19).as_deref(),
+            Some("KaigiShiryou.tar.gz")
+        );
+        assert_eq!(truncate(".settei_file", 8).as_deref(), Some(".settei"));
+        // No boundary within the limit: cut between characters
+        assert_eq!(truncate("toukyouto.txt", 9).as_deref(), Some("touky.txt"));
+        assert_eq!(truncate("東京都.txt", 9).as_deref(), Some("東.txt"));
+        assert_eq!(truncate("kaigi.pdf", 4), None);
+        assert_eq!(truncate("kaigi.pdf", 9).as_deref(), Some("kaigi.pdf"));
     }
 }
@@ -0,0 +1,91 @@
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// User dictionary of readings that override kakasi, e.g. for proper
+/// nouns kakasi reads wrongly.
+///
+/// The file format is one `surface<TAB>romaji` pair per line. Empty lines
+/// and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    entries: HashMap<String, String>,
+    /// Length of the longest surface form, in characters
+    max_chars: usize,
+}
+
+impl Dictionary {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut dict = Dictionary::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((surface, romaji)) = line.split_once('\t') else {
+                bail!("line {}: expected `surface<TAB>romaji`", i + 1);
+            };
+            let (surface, romaji) = (surface.trim(), romaji.trim());
+            if surface.is_empty() || romaji.is_empty() {
+                bail!("line {}: empty surface or romaji", i + 1);
+            }
+            dict.insert(surface, romaji);
+        }
+        Ok(dict)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read dictionary {}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| anyhow!("{}: {e}", path.display()))
+    }
+
+    pub fn insert(&mut self, surface: &str, romaji: &str) {
+        self.max_chars = self.max_chars.max(surface.chars().count());
+        self.entries.insert(surface.to_string(), romaji.to_string());
+    }
+
+    /// Find the longest entry `s` starts with. Returns the byte length of
+    /// the matched surface form and its romaji.
+    pub fn longest_match(&self, s: &str) -> Option<(usize, &str)> {
+        let ends: Vec<usize> = s
+            .char_indices()
+            .map(|(i, c)| i + c.len_utf8())
+            .take(self.max_chars)
+            .collect();
+        ends.into_iter().rev().find_map(|end| {
+            self.entries
+                .get(&s[..end])
+                .map(|romaji| (end, romaji.as_str()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let dict = Dictionary::parse("# names\n\n東海林\tshoji\n").unwrap();
+        assert_eq!(
+            dict.longest_match("東海林"),
+            Some(("東海林".len(), "shoji"))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_missing_tab() {
+        assert!(Dictionary::parse("東海林 shoji\n").is_err());
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let dict = Dictionary::parse("東\thigashi\n東海林\tshoji\n").unwrap();
+        assert_eq!(
+            dict.longest_match("東海林さん"),
+            Some(("東海林".len(), "shoji"))
+        );
+        assert_eq!(dict.longest_match("東京"), Some(("東".len(), "higashi")));
+        assert_eq!(dict.longest_match("京都"), None);
+    }
+}
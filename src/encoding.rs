@@ -0,0 +1,94 @@
+use clap::ValueEnum;
+use encoding_rs::{EUC_JP, SHIFT_JIS};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+/// Legacy encoding of filenames that are not valid UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// Shift_JIS (CP932), as written by Japanese Windows
+    Sjis,
+    /// EUC-JP, as written by older Unix systems
+    Eucjp,
+    /// Whichever of Shift_JIS and EUC-JP decodes to more Japanese text
+    Auto,
+}
+
+fn decode_as(bytes: &[u8], encoding: &'static encoding_rs::Encoding) -> Option<String> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|s| s.into_owned())
+}
+
+/// Number of kana and kanji, used to pick the likelier decoding.
+fn japanese_chars(s: &str) -> usize {
+    s.chars()
+        .filter(|c| matches!(c, '\u{3040}'..='\u{30FF}' | '\u{4E00}'..='\u{9FFF}'))
+        .count()
+}
+
+/// Decode a filename. Valid UTF-8 is returned as is; anything else is
+/// decoded from `encoding`, or `None` if it is not valid there either.
+pub fn decode(name: &OsStr, encoding: Option<Encoding>) -> Option<String> {
+    if let Some(s) = name.to_str() {
+        return Some(s.to_string());
+    }
+    let bytes = name.as_bytes();
+    match encoding? {
+        Encoding::Sjis => decode_as(bytes, SHIFT_JIS),
+        Encoding::Eucjp => decode_as(bytes, EUC_JP),
+        Encoding::Auto => match (decode_as(bytes, SHIFT_JIS), decode_as(bytes, EUC_JP)) {
+            (Some(sjis), Some(eucjp)) if japanese_chars(&eucjp) > japanese_chars(&sjis) => {
+                Some(eucjp)
+            }
+            (Some(sjis), _) => Some(sjis),
+            (None, eucjp) => eucjp,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(s: &str, encoding: &'static encoding_rs::Encoding) -> Vec<u8> {
+        encoding.encode(s).0.into_owned()
+    }
+
+    #[test]
+    fn utf8_is_kept() {
+        assert_eq!(
+            decode(OsStr::new("テスト.txt"), Some(Encoding::Sjis)).as_deref(),
+            Some("テスト.txt")
+        );
+    }
+
+    #[test]
+    fn explicit_encodings() {
+        let sjis = encode("写真.jpg", SHIFT_JIS);
+        let eucjp = encode("写真.jpg", EUC_JP);
+        let decoded = |bytes: &[u8], encoding| decode(OsStr::from_bytes(bytes), encoding);
+        assert_eq!(
+            decoded(&sjis, Some(Encoding::Sjis)).as_deref(),
+            Some("写真.jpg")
+        );
+        assert_eq!(
+            decoded(&eucjp, Some(Encoding::Eucjp)).as_deref(),
+            Some("写真.jpg")
+        );
+        assert_eq!(decoded(&sjis, None), None);
+    }
+
+    #[test]
+    fn auto_detects_either() {
+        for encoding in [SHIFT_JIS, EUC_JP] {
+            let bytes = encode("日本語のファイル.txt", encoding);
+            assert_eq!(
+                decode(OsStr::from_bytes(&bytes), Some(Encoding::Auto)).as_deref(),
+                Some("日本語のファイル.txt"),
+                "{}",
+                encoding.name()
+            );
+        }
+    }
+}
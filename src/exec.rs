@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::plan::{self, EntryStatus, RenamePlan};
+use crate::rename::{self, RenameError};
+
+/// A rename [`RenamePlan::execute`] could not make.
+#[derive(Debug)]
+pub struct Failure {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub error: RenameError,
+    /// Temporary name the file was left under, if it had been moved aside
+    /// and its original name was taken again when putting it back
+    pub left_at: Option<PathBuf>,
+}
+
+/// Outcome of [`RenamePlan::execute`].
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub renamed: usize,
+    /// Targets of the successful renames, in the order they were made.
+    /// Each is where the file is after the whole batch, so it follows
+    /// directories along its path that were renamed later.
+    pub targets: Vec<PathBuf>,
+    /// Renames that were tried and failed, in the order they were tried
+    pub failures: Vec<Failure>,
+    /// Failed renames, including entries that were not ready
+    pub errors: usize,
+}
+
+/// Drop `.` components, so `./a/b` is inside `a`.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .collect()
+}
+
+/// Where `path` ends up after `renames`, applied in order, moved it or a
+/// directory it is in.
+fn follow(path: &Path, renames: &[(PathBuf, PathBuf)]) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for (source, target) in renames {
+        if let Ok(rest) = normalize(&path).strip_prefix(normalize(source)) {
+            path = if rest.as_os_str().is_empty() {
+                target.clone()
+            } else {
+                target.join(rest)
+            };
+        }
+    }
+    path
+}
+
+impl RenamePlan {
+    /// Rename the entries for which `accepted` is true, in plan order,
+    /// calling `on_rename` with the source and target of every rename made
+    /// (for example to journal it).
+    ///
+    /// Statuses are recomputed for the accepted entries alone, since a
+    /// declined entry no longer vacates its source. When a target is still
+    /// occupied by the source of a pending entry (a chain `A → B`, `B → C`
+    /// or a cycle `A ↔ B`), that source is first moved aside to a temporary
+    /// name and renamed from there when its own turn comes.
+    ///
+    /// ```
+    /// use romv::{Converter, EntryStatus, RenamePlan};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let file = dir.path().join("テスト.txt");
+    /// std::fs::write(&file, "").unwrap();
+    ///
+    /// let plan = RenamePlan::build(&[file], &Converter::default());
+    /// assert_eq!(plan.entries[0].target, dir.path().join("tesuto.txt"));
+    /// assert_eq!(plan.entries[0].status, EntryStatus::Ready);
+    ///
+    /// let summary = plan.execute(&[true], |_, _| {});
+    /// assert_eq!(summary.renamed, 1);
+    /// assert!(dir.path().join("tesuto.txt").exists());
+    /// ```
+    pub fn execute(&self, accepted: &[bool], mut on_rename: impl FnMut(&Path, &Path)) -> Summary {
+        let mut entries: Vec<_> = self
+            .entries
+            .iter()
+            .zip(accepted)
+            .filter(|(entry, accepted)| **accepted && entry.status == EntryStatus::Ready)
+            .map(|(entry, _)| entry.clone())
+            .collect();
+        let not_ready = self
+            .entries
+            .iter()
+            .filter(|e| e.status != EntryStatus::Ready)
+            .count();
+        plan::update_statuses(&mut entries);
+
+        let sources: HashMap<PathBuf, usize> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.source.clone(), i))
+            .collect();
+        // Where each entry's file currently is
+        let mut location: Vec<PathBuf> = entries.iter().map(|e| e.source.clone()).collect();
+        let mut done = vec![false; entries.len()];
+        // Every rename made, and for each target the number made until then
+        let mut made: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let mut renamed = |source: &Path, target: &Path| {
+            on_rename(source, target);
+            made.push((source.to_path_buf(), target.to_path_buf()));
+            made.len()
+        };
+        let mut targets = Vec::new();
+        let mut failures = Vec::new();
+        let mut fail = |source: &Path, target: &Path, error, left_at| {
+            failures.push(Failure {
+                source: source.to_path_buf(),
+                target: target.to_path_buf(),
+                error,
+                left_at,
+            })
+        };
+
+        for (i, entry) in entries.iter().enumerate() {
+            done[i] = true;
+            if entry.status != EntryStatus::Ready {
+                fail(
+                    &entry.source,
+                    &entry.target,
+                    RenameError::TargetExists(entry.target.clone()),
+                    None,
+                );
+                continue;
+            }
+
+            if let Some(&j) = sources.get(&entry.target)
+                && !done[j]
+                && location[j] == entries[j].source
+            {
+                let temp = rename::temp_name(&entries[j].source, j);
+                if let Err(e) = rename::safe_rename(&entries[j].source, &temp) {
+                    fail(&entry.source, &entry.target, e, None);
+                    continue;
+                }
+                renamed(&entries[j].source, &temp);
+                location[j] = temp;
+            }
+
+            if let Some(backup) = &entry.backup {
+                if let Err(e) = rename::safe_rename(&entry.target, backup) {
+                    fail(&entry.target, backup, e, None);
+                    continue;
+                }
+                renamed(&entry.target, backup);
+            }
+
+            match rename::safe_rename(&location[i], &entry.target) {
+                Ok(()) => {
+                    let n = renamed(&location[i], &entry.target);
+                    targets.push((n, entry.target.clone()));
+                }
+                Err(e) => {
+                    // Put a file that was moved aside back where it was
+                    let mut left_at = None;
+                    if location[i] != entry.source {
+                        match rename::safe_rename(&location[i], &entry.source) {
+                            Ok(()) => {
+                                renamed(&location[i], &entry.source);
+                            }
+                            Err(_) => left_at = Some(location[i].clone()),
+                        }
+                    }
+                    fail(&entry.source, &entry.target, e, left_at);
+                }
+            }
+        }
+
+        let targets: Vec<PathBuf> = targets
+            .into_iter()
+            .map(|(n, target)| follow(&target, &made[n..]))
+            .collect();
+        Summary {
+            renamed: targets.len(),
+            targets,
+            errors: not_ready + failures.len(),
+            failures,
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use glob::Pattern;
+use regex::Regex;
+use std::fs::Metadata;
+use std::path::Path;
+
+/// Kind of file a [`Filter`] can be restricted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FileType {
+    /// Regular files
+    #[value(name = "f")]
+    File,
+    /// Directories
+    #[value(name = "d")]
+    Dir,
+    /// Symbolic links
+    #[value(name = "l")]
+    Symlink,
+}
+
+impl FileType {
+    fn matches(self, metadata: &Metadata) -> bool {
+        let file_type = metadata.file_type();
+        match self {
+            FileType::File => file_type.is_file(),
+            FileType::Dir => file_type.is_dir(),
+            FileType::Symlink => file_type.is_symlink(),
+        }
+    }
+}
+
+/// Which paths [`RenamePlan::build_filtered`] considers at all.
+///
+/// Globs without a `/` are matched against the file name, others against
+/// the whole path; the regex is searched for in the file name.
+///
+/// ```
+/// use romv::{FileType, Filter};
+///
+/// let filter = Filter::default()
+///     .include("*.jpg")
+///     .unwrap()
+///     .exclude("*_thumb.*")
+///     .unwrap()
+///     .file_type(FileType::File);
+/// ```
+///
+/// [`RenamePlan::build_filtered`]: crate::RenamePlan::build_filtered
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    regex: Option<Regex>,
+    file_type: Option<FileType>,
+}
+
+fn glob(pattern: &str) -> Result<Pattern> {
+    Pattern::new(pattern).map_err(|e| anyhow!("invalid glob {pattern:?}: {e}"))
+}
+
+fn glob_matches(pattern: &Pattern, path: &Path) -> bool {
+    if pattern.as_str().contains('/') {
+        pattern.matches_path(path)
+    } else {
+        path.file_name()
+            .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+    }
+}
+
+impl Filter {
+    /// Only consider paths matching `pattern` (or any other include).
+    pub fn include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(glob(pattern)?);
+        Ok(self)
+    }
+
+    /// Never consider paths matching `pattern`.
+    pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(glob(pattern)?);
+        Ok(self)
+    }
+
+    /// Only consider file names in which `regex` matches.
+    pub fn regex(mut self, regex: &str) -> Result<Self> {
+        self.regex = Some(Regex::new(regex).map_err(|e| anyhow!("invalid regex: {e}"))?);
+        Ok(self)
+    }
+
+    /// Only consider files of this type.
+    pub fn file_type(mut self, file_type: FileType) -> Self {
+        self.file_type = Some(file_type);
+        self
+    }
+
+    /// Whether `path`, with its `symlink_metadata`, passes the filter.
+    pub fn accepts(&self, path: &Path, metadata: &Metadata) -> bool {
+        if self.file_type.is_some_and(|t| !t.matches(metadata)) {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|p| glob_matches(p, path)) {
+            return false;
+        }
+        if self.exclude.iter().any(|p| glob_matches(p, path)) {
+            return false;
+        }
+        self.regex.as_ref().is_none_or(|regex| {
+            path.file_name()
+                .is_some_and(|name| regex.is_match(&name.to_string_lossy()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn globs_regex_and_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("写真.jpg");
+        let thumb = dir.path().join("写真_thumb.jpg");
+        let sub = dir.path().join("資料");
+        fs::write(&photo, "").unwrap();
+        fs::write(&thumb, "").unwrap();
+        fs::create_dir(&sub).unwrap();
+        let accepts =
+            |filter: &Filter, path: &Path| filter.accepts(path, &path.symlink_metadata().unwrap());
+
+        let filter = Filter::default()
+            .include("*.jpg")
+            .unwrap()
+            .exclude("*_thumb.*")
+            .unwrap();
+        assert!(accepts(&filter, &photo));
+        assert!(!accepts(&filter, &thumb));
+        assert!(!accepts(&filter, &sub));
+
+        let filter = Filter::default().file_type(FileType::Dir);
+        assert!(accepts(&filter, &sub));
+        assert!(!accepts(&filter, &photo));
+
+        let filter = Filter::default().regex("^写真_").unwrap();
+        assert!(accepts(&filter, &thumb));
+        assert!(!accepts(&filter, &photo));
+
+        let filter = Filter::default().include("**/資料").unwrap();
+        assert!(accepts(&filter, &sub));
+
+        assert!(Filter::default().include("[").is_err());
+        assert!(Filter::default().regex("(").is_err());
+    }
+}
@@ -0,0 +1,389 @@
+use anyhow::{Result, anyhow, bail};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+use romv::{EntryStatus, RenameEntry, RenamePlan};
+
+/// Output format of the rename plan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Colored `source -> target` lines
+    #[default]
+    Human,
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Tab-separated `source`, `target`, `status`, `reason`, `backup` columns
+    Tsv,
+}
+
+/// One line of an exported plan. Skipped and filtered paths carry the
+/// reason they were left out, and a conflict skip the target it lost.
+///
+/// Paths are written as strings; one that is not valid UTF-8 is written
+/// lossily and also as an array of its bytes in the `*_bytes` field,
+/// which takes precedence when the plan is read back.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Record {
+    source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_bytes: Option<Vec<u8>>,
+    target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target_bytes: Option<Vec<u8>>,
+    status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    /// Where an existing target will be moved (`--on-conflict overwrite-backup`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backup: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backup_bytes: Option<Vec<u8>>,
+}
+
+/// Statuses of paths that are not renamed, ignored when a plan is read.
+const LEFT_OUT: &[&str] = &["skipped", "filtered"];
+
+/// `path` as a string, and its bytes if it is not valid UTF-8.
+fn text(path: &Path) -> (String, Option<Vec<u8>>) {
+    let bytes = path
+        .to_str()
+        .is_none()
+        .then(|| path.as_os_str().as_bytes().to_vec());
+    (path.to_string_lossy().into_owned(), bytes)
+}
+
+fn path(text: String, bytes: Option<Vec<u8>>) -> PathBuf {
+    match bytes {
+        Some(bytes) => PathBuf::from(OsString::from_vec(bytes)),
+        None => PathBuf::from(text),
+    }
+}
+
+/// A line of the plan before it is formatted.
+struct Row<'a> {
+    source: &'a Path,
+    target: Option<&'a Path>,
+    status: &'static str,
+    reason: Option<&'static str>,
+    backup: Option<&'a Path>,
+}
+
+fn rows(plan: &RenamePlan) -> Vec<Row<'_>> {
+    let entries = plan.entries.iter().map(|entry| Row {
+        source: &entry.source,
+        target: Some(&entry.target),
+        status: entry.status.as_str(),
+        reason: None,
+        backup: entry.backup.as_deref(),
+    });
+    let skipped = plan.skipped.iter().map(|skip| Row {
+        source: &skip.source,
+        target: skip.target.as_deref(),
+        status: "skipped",
+        reason: Some(skip.reason.as_str()),
+        backup: None,
+    });
+    let filtered = plan.filtered.iter().map(|source| Row {
+        source,
+        target: None,
+        status: "filtered",
+        reason: None,
+        backup: None,
+    });
+    entries.chain(skipped).chain(filtered).collect()
+}
+
+fn record(row: &Row) -> Record {
+    let (source, source_bytes) = text(row.source);
+    let (target, target_bytes) = row.target.map(text).unzip();
+    let (backup, backup_bytes) = row.backup.map(text).unzip();
+    Record {
+        source,
+        source_bytes,
+        target,
+        target_bytes: target_bytes.flatten(),
+        status: row.status.to_string(),
+        reason: row.reason.map(str::to_string),
+        backup,
+        backup_bytes: backup_bytes.flatten(),
+    }
+}
+
+fn tsv_field(path: &Path) -> Result<&[u8]> {
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.contains(&b'\t') || bytes.contains(&b'\n') {
+        bail!(
+            "path contains a tab or newline, cannot write TSV: {:?}",
+            path.to_string_lossy()
+        );
+    }
+    Ok(bytes)
+}
+
+/// Write every entry of `plan`, including skipped and filtered paths, to
+/// `out` in a machine-readable `format`. Nothing is written if the plan
+/// cannot be represented in `format`.
+///
+/// TSV paths are written as raw bytes, like `find` prints them.
+pub fn write_plan(plan: &RenamePlan, format: Format, out: &mut impl Write) -> Result<()> {
+    let rows = rows(plan);
+    let mut buf = Vec::new();
+    match format {
+        Format::Human => bail!("human format is printed by report::plan"),
+        Format::Json => {
+            let records: Vec<Record> = rows.iter().map(record).collect();
+            serde_json::to_writer_pretty(&mut buf, &records)?;
+            buf.push(b'\n');
+        }
+        Format::Ndjson => {
+            for row in &rows {
+                serde_json::to_writer(&mut buf, &record(row))?;
+                buf.push(b'\n');
+            }
+        }
+        Format::Tsv => {
+            for row in &rows {
+                buf.extend_from_slice(tsv_field(row.source)?);
+                buf.push(b'\t');
+                if let Some(target) = row.target {
+                    buf.extend_from_slice(tsv_field(target)?);
+                }
+                write!(
+                    buf,
+                    "\t{}\t{}\t",
+                    row.status,
+                    row.reason.unwrap_or_default()
+                )?;
+                if let Some(backup) = row.backup {
+                    buf.extend_from_slice(tsv_field(backup)?);
+                }
+                buf.push(b'\n');
+            }
+        }
+    }
+    out.write_all(&buf)?;
+    Ok(())
+}
+
+fn entry(source: PathBuf, target: PathBuf, backup: Option<PathBuf>) -> RenameEntry {
+    RenameEntry {
+        source,
+        target,
+        status: EntryStatus::Ready,
+        backup,
+        fixes: Vec::new(),
+    }
+}
+
+/// Parse an exported plan back into the renames it lists. The format
+/// (JSON, NDJSON or TSV) is detected from the content. Records without a
+/// target, or skipped or filtered, are ignored; statuses are not trusted
+/// and get recomputed by [`RenamePlan::from_entries`].
+pub fn parse_plan(input: &[u8]) -> Result<Vec<RenameEntry>> {
+    let lines = || {
+        input
+            .split(|&b| b == b'\n')
+            .enumerate()
+            .filter(|(_, line)| !line.trim_ascii().is_empty())
+    };
+    let records: Vec<Record> = match input.trim_ascii_start().first() {
+        None => Vec::new(),
+        Some(b'[') => serde_json::from_slice(input)?,
+        Some(b'{') => lines()
+            .map(|(i, line)| {
+                serde_json::from_slice(line).map_err(|e| anyhow!("line {}: {e}", i + 1))
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => {
+            let path = |bytes: &[u8]| PathBuf::from(OsStr::from_bytes(bytes));
+            let mut entries = Vec::new();
+            for (i, line) in lines() {
+                let mut fields = line.split(|&b| b == b'\t');
+                let (Some(source), target) = (fields.next(), fields.next()) else {
+                    bail!("line {}: expected `source<TAB>target`", i + 1);
+                };
+                let status = fields.next().unwrap_or_default();
+                let backup = fields.nth(1).filter(|b| !b.is_empty());
+                if let Some(target) = target.filter(|t| !t.is_empty())
+                    && !LEFT_OUT.iter().any(|s| s.as_bytes() == status)
+                {
+                    entries.push(entry(path(source), path(target), backup.map(path)));
+                }
+            }
+            return Ok(entries);
+        }
+    };
+
+    Ok(records
+        .into_iter()
+        .filter(|r| !LEFT_OUT.contains(&r.status.as_str()))
+        .filter_map(|r| {
+            let target = path(r.target?, r.target_bytes);
+            let backup = r.backup.map(|backup| path(backup, r.backup_bytes));
+            Some(entry(path(r.source, r.source_bytes), target, backup))
+        })
+        .collect())
+}
+
+pub fn read_plan(path: &Path) -> Result<Vec<RenameEntry>> {
+    let input =
+        std::fs::read(path).map_err(|e| anyhow!("cannot read plan {}: {e}", path.display()))?;
+    parse_plan(&input).map_err(|e| anyhow!("{}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use romv::{SkipReason, SkippedEntry};
+
+    fn sample() -> RenamePlan {
+        RenamePlan {
+            entries: vec![RenameEntry {
+                source: PathBuf::from("dir/テスト.txt"),
+                target: PathBuf::from("dir/tesuto.txt"),
+                status: EntryStatus::Ready,
+                backup: None,
+                fixes: Vec::new(),
+            }],
+            skipped: vec![SkippedEntry {
+                source: PathBuf::from("hello.txt"),
+                reason: SkipReason::Unchanged,
+                target: None,
+            }],
+            filtered: Vec::new(),
+        }
+    }
+
+    fn parse(input: &[u8]) -> Vec<(PathBuf, PathBuf, Option<PathBuf>)> {
+        parse_plan(input)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.source, e.target, e.backup))
+            .collect()
+    }
+
+    fn export(format: Format) -> String {
+        let mut out = Vec::new();
+        write_plan(&sample(), format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn ndjson_lists_skipped_with_reason() {
+        let out = export(Format::Ndjson);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            r#"{"source":"dir/テスト.txt","target":"dir/tesuto.txt","status":"ready"}"#
+        );
+        assert_eq!(
+            lines[1],
+            r#"{"source":"hello.txt","target":null,"status":"skipped","reason":"unchanged"}"#
+        );
+    }
+
+    #[test]
+    fn tsv_columns() {
+        assert_eq!(
+            export(Format::Tsv),
+            "dir/テスト.txt\tdir/tesuto.txt\tready\t\t\nhello.txt\t\tskipped\tunchanged\t\n"
+        );
+    }
+
+    #[test]
+    fn round_trip_every_format() {
+        let expected = vec![(
+            PathBuf::from("dir/テスト.txt"),
+            PathBuf::from("dir/tesuto.txt"),
+            None,
+        )];
+        for format in [Format::Json, Format::Ndjson, Format::Tsv] {
+            assert_eq!(parse(export(format).as_bytes()), expected, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn non_utf8_paths_round_trip() {
+        // "テスト.txt" in Shift_JIS
+        let source = PathBuf::from(OsStr::from_bytes(b"dir/\x83\x65\x83\x58\x83\x67.txt"));
+        let mut plan = sample();
+        plan.entries[0].source = source.clone();
+        for format in [Format::Json, Format::Ndjson, Format::Tsv] {
+            let mut out = Vec::new();
+            write_plan(&plan, format, &mut out).unwrap();
+            assert_eq!(
+                parse(&out),
+                vec![(source.clone(), PathBuf::from("dir/tesuto.txt"), None)],
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn nothing_written_for_unrepresentable_tsv() {
+        let mut plan = sample();
+        plan.skipped[0].source = PathBuf::from("a\tb.txt");
+        let mut out = Vec::new();
+        assert!(write_plan(&plan, Format::Tsv, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn parse_hand_written_tsv() {
+        assert_eq!(
+            parse(b"a.txt\tb.txt\n"),
+            vec![(PathBuf::from("a.txt"), PathBuf::from("b.txt"), None)]
+        );
+    }
+
+    #[test]
+    fn backups_conflicts_and_filtered_round_trip() {
+        // "テスト.txt~" in Shift_JIS
+        let backup = PathBuf::from(OsStr::from_bytes(b"dir/\x83\x65\x83\x58\x83\x67.txt~"));
+        let mut plan = sample();
+        plan.entries[0].backup = Some(backup.clone());
+        plan.skipped[0] = SkippedEntry {
+            source: PathBuf::from("dir/テスト.TXT"),
+            reason: SkipReason::Conflict,
+            target: Some(PathBuf::from("dir/tesuto.txt")),
+        };
+        plan.filtered.push(PathBuf::from("dir/写真.jpg"));
+
+        let mut out = Vec::new();
+        write_plan(&plan, Format::Ndjson, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(
+            lines[0].contains(r#""backup_bytes":[100,105,114,47,131,101"#),
+            "{out}"
+        );
+        assert_eq!(
+            lines[1],
+            r#"{"source":"dir/テスト.TXT","target":"dir/tesuto.txt","status":"skipped","reason":"conflict"}"#
+        );
+        assert_eq!(
+            lines[2],
+            r#"{"source":"dir/写真.jpg","target":null,"status":"filtered"}"#
+        );
+
+        for format in [Format::Json, Format::Ndjson, Format::Tsv] {
+            let mut out = Vec::new();
+            write_plan(&plan, format, &mut out).unwrap();
+            assert_eq!(
+                parse(&out),
+                vec![(
+                    PathBuf::from("dir/テスト.txt"),
+                    PathBuf::from("dir/tesuto.txt"),
+                    Some(backup.clone())
+                )],
+                "{format:?}"
+            );
+        }
+    }
+}
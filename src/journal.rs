@@ -0,0 +1,254 @@
+use anyhow::{Result, anyhow};
+use romv::{RenameError, safe_rename};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::xdg;
+
+/// One completed rename, stored as a line of JSON in the journal.
+///
+/// Paths are stored as strings, or as arrays of bytes if they are not
+/// valid UTF-8 (e.g. legacy names renamed with `--from-encoding`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// Identifies the romv invocation that performed the rename
+    pub batch: String,
+    #[serde(with = "raw_path")]
+    pub source: PathBuf,
+    #[serde(with = "raw_path")]
+    pub target: PathBuf,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    #[serde(with = "raw_path")]
+    pub cwd: PathBuf,
+}
+
+/// Lossless (de)serialization of paths that may not be UTF-8.
+mod raw_path {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Raw<'a> {
+        Utf8(&'a str),
+        Bytes(&'a [u8]),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Owned {
+        Utf8(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(s) => Raw::Utf8(s),
+            None => Raw::Bytes(path.as_os_str().as_bytes()),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Ok(match Owned::deserialize(deserializer)? {
+            Owned::Utf8(s) => PathBuf::from(s),
+            Owned::Bytes(bytes) => PathBuf::from(OsStr::from_bytes(&bytes)),
+        })
+    }
+}
+
+/// Default journal location: `$XDG_STATE_HOME/romv/journal.jsonl`.
+pub fn default_path() -> Result<PathBuf> {
+    xdg::state_home()
+        .map(|dir| dir.join("romv").join("journal.jsonl"))
+        .ok_or_else(|| anyhow!("cannot determine journal location: HOME is not set"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Open the journal at `path` for appending, holding an exclusive lock
+/// until the file is closed. A journal replaced by [`undo`] while
+/// waiting for the lock is opened again, so no record goes to the old one.
+fn open_locked(path: &Path) -> io::Result<File> {
+    loop {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        // SAFETY: the descriptor belongs to `file`, which outlives the call
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (locked, current) = (file.metadata()?, fs::metadata(path));
+        if current.is_ok_and(|m| m.dev() == locked.dev() && m.ino() == locked.ino()) {
+            return Ok(file);
+        }
+    }
+}
+
+/// Appends the renames of one batch to a journal file. The file is only
+/// created once the first rename is recorded.
+pub struct Journal {
+    path: PathBuf,
+    batch: String,
+    cwd: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        Ok(Journal {
+            path,
+            batch: format!("{}-{}", now(), std::process::id()),
+            cwd: std::env::current_dir()?,
+        })
+    }
+
+    /// Record a successful rename. Paths are stored as absolute paths so
+    /// the journal can be replayed from any directory.
+    pub fn append(&mut self, source: &Path, target: &Path) -> Result<()> {
+        let record = Record {
+            batch: self.batch.clone(),
+            source: self.cwd.join(source),
+            target: self.cwd.join(target),
+            timestamp: now(),
+            cwd: self.cwd.clone(),
+        };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        open_locked(&self.path)?.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+pub fn read(path: &Path) -> Result<Vec<Record>> {
+    let file =
+        File::open(path).map_err(|e| anyhow!("cannot open journal {}: {e}", path.display()))?;
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| anyhow!("{}:{}: {e}", path.display(), i + 1))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Remove `reverted` from the journal at `path`. The journal is read
+/// again under lock, so records appended since are kept, and replaced
+/// through a temporary file, so a crash leaves either version whole.
+fn remove(path: &Path, reverted: &[Record]) -> Result<()> {
+    let _lock = open_locked(path)?;
+    let mut records = read(path)?;
+    records.retain(|record| !reverted.contains(record));
+
+    let mut out = String::new();
+    for record in &records {
+        out.push_str(&serde_json::to_string(record)?);
+        out.push('\n');
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut file = File::create(&temp)?;
+    file.write_all(out.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// A journaled rename selected by [`undo`].
+#[derive(Debug)]
+pub struct Undone {
+    pub record: Record,
+    /// Why the rename could not be reverted
+    pub error: Option<RenameError>,
+}
+
+/// Revert the renames of the last `last` batches in the journal at
+/// `path`, most recent first, with the same no-clobber checks as a
+/// normal rename. Reverted records are removed from the journal.
+///
+/// Returns the selected renames in the order they were (or, without
+/// `execute`, would be) reverted.
+pub fn undo(path: &Path, last: usize, execute: bool) -> Result<Vec<Undone>> {
+    let records = read(path)?;
+
+    let mut batches: Vec<&str> = Vec::new();
+    for record in records.iter().rev() {
+        if !batches.contains(&record.batch.as_str()) {
+            if batches.len() == last {
+                break;
+            }
+            batches.push(&record.batch);
+        }
+    }
+    let selected: Vec<usize> = (0..records.len())
+        .rev()
+        .filter(|&i| batches.contains(&records[i].batch.as_str()))
+        .collect();
+
+    let mut undone = Vec::new();
+    let mut reverted = Vec::new();
+    for i in selected {
+        let record = &records[i];
+        let error = if execute {
+            safe_rename(&record.target, &record.source).err()
+        } else {
+            None
+        };
+        if execute && error.is_none() {
+            reverted.push(record.clone());
+        }
+        undone.push(Undone {
+            record: record.clone(),
+            error,
+        });
+    }
+
+    if !reverted.is_empty() {
+        remove(path, &reverted)?;
+    }
+
+    Ok(undone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_keeps_records_appended_meanwhile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let mut journal = Journal::new(path.clone()).unwrap();
+        journal.append(Path::new("a"), Path::new("b")).unwrap();
+        let reverted = read(&path).unwrap();
+        journal.append(Path::new("c"), Path::new("d")).unwrap();
+
+        remove(&path, &reverted).unwrap();
+        let records = read(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].source.ends_with("c"));
+        assert!(!dir.path().join("journal.jsonl.tmp").exists());
+
+        // Appends after the rewrite go to the new journal
+        journal.append(Path::new("e"), Path::new("f")).unwrap();
+        assert_eq!(read(&path).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,41 @@
+//! Rename Japanese filenames to romaji.
+//!
+//! [`Converter`] turns a single filename into romaji; [`RenamePlan`]
+//! plans renames for a set of paths, checks them for conflicts and
+//! carries them out without overwriting existing files.
+//!
+//! ```
+//! use romv::{Case, Converter};
+//!
+//! let converter = Converter::builder().case(Case::Kebab).build().unwrap();
+//! assert_eq!(converter.convert("新しいファイル.txt"), "atarashii-fairu.txt");
+//! ```
+
+mod case;
+mod convert;
+mod dict;
+mod encoding;
+mod exec;
+mod filter;
+mod normalize;
+mod plan;
+mod punct;
+mod rename;
+mod romaji;
+mod sanitize;
+mod segment;
+mod translit;
+
+pub use case::Case;
+pub use convert::{Converter, ConverterBuilder, MULTI_EXTENSIONS};
+pub use dict::Dictionary;
+pub use encoding::Encoding;
+pub use exec::{Failure, Summary};
+pub use filter::{FileType, Filter};
+pub use normalize::Form;
+pub use plan::{EntryStatus, OnConflict, RenameEntry, RenamePlan, SkipReason, SkippedEntry};
+pub use punct::{DEFAULT_MAPPINGS, Punctuation, parse_mapping};
+pub use rename::{RenameError, safe_rename};
+pub use romaji::{LongVowels, System};
+pub use sanitize::{Fix, Sanitize};
+pub use translit::Script;
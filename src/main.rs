@@ -1,58 +1,284 @@
 mod cli;
-mod convert;
-mod plan;
-mod rename;
+mod config;
+mod format;
+mod journal;
+mod report;
+mod walk;
+mod xdg;
 
 use anyhow::{Result, bail};
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
 use colored::Colorize;
-use plan::EntryStatus;
+use romv::{Converter, Dictionary, EntryStatus, Filter, Punctuation, RenamePlan};
+use std::ffi::OsStr;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::os::unix::ffi::OsStrExt;
 use std::path::PathBuf;
 
+fn undo(args: &cli::UndoArgs) -> Result<()> {
+    let path = match &args.journal {
+        Some(path) => path.clone(),
+        None => {
+            let path = journal::default_path()?;
+            // Nothing has been renamed yet
+            if path.symlink_metadata().is_err() {
+                eprintln!("Nothing to undo.");
+                return Ok(());
+            }
+            path
+        }
+    };
+    let undone = journal::undo(&path, args.last, args.yes)?;
+    if undone.is_empty() {
+        eprintln!("Nothing to undo.");
+        return Ok(());
+    }
+
+    let mut errors = 0;
+    for journal::Undone { record, error } in &undone {
+        match error {
+            None => println!(
+                "{} {} {}{}",
+                record.target.display(),
+                "->".bold(),
+                record.source.display().to_string().green(),
+                if args.yes { "" } else { " (dry-run)" }
+            ),
+            Some(e) => {
+                eprintln!(
+                    "{} {} -> {}: {e}",
+                    "error:".red().bold(),
+                    record.target.display(),
+                    record.source.display()
+                );
+                errors += 1;
+            }
+        }
+    }
+    if !args.yes {
+        eprintln!(
+            "\n{}",
+            "Dry-run complete. Use `romv undo -y` to execute.".dimmed()
+        );
+    }
+    if errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn show_config(profile: Option<&str>) -> Result<()> {
+    let config = config::load(profile)?;
+    let matches = cli::Args::command().get_matches_from(["romv"]);
+    let mut args = cli::Args::from_arg_matches(&matches)?;
+    config.settings.apply(&mut args, &matches);
+
+    for file in &config.files {
+        println!("# {}", file.display());
+    }
+    if let Some(profile) = profile {
+        println!("# profile: {profile}");
+    }
+    print!("{}", toml::to_string(&config::Settings::from_args(&args))?);
+    Ok(())
+}
+
+/// Check the constraints between options. Both values must come from
+/// the command line to be an error: a config `format` gives way to
+/// `--print0`, and a config `max-depth` is dropped unless recursing.
+fn validate(args: &mut cli::Args, matches: &ArgMatches) {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let mut command = cli::Args::command();
+    if args.print0 && args.format != format::Format::Human {
+        if given("format") {
+            command
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--print0 cannot be used with --format",
+                )
+                .exit();
+        }
+        args.format = format::Format::Human;
+    }
+    if !args.recursive {
+        if args.max_depth.is_some() && given("max_depth") {
+            command
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "--max-depth requires --recursive",
+                )
+                .exit();
+        }
+        args.max_depth = None;
+    }
+}
+
+/// Build the converter from the naming options.
+fn converter(args: &cli::Args) -> Result<Converter> {
+    let dictionary = match &args.dict {
+        Some(path) => Dictionary::load(path)?,
+        None => match config::dict_path() {
+            Some(path) if path.exists() => Dictionary::load(&path)?,
+            _ => Dictionary::default(),
+        },
+    };
+
+    let mut punctuation = Punctuation::default();
+    for (from, to) in &args.map {
+        punctuation.insert(*from, to)?;
+    }
+
+    let mut builder = Converter::builder()
+        .separator(args.separator)
+        .system(args.system)
+        .case(args.case)
+        .dictionary(dictionary)
+        .output_form(args.output_form)
+        .normalize_only(args.normalize_only)
+        .punctuation(punctuation)
+        .scripts(args.scripts.iter().copied())
+        .romanize_extension(args.romanize_ext)
+        .lowercase_extension(args.lowercase_ext);
+    for extension in &args.multi_ext {
+        builder = builder.multi_extension(extension);
+    }
+    if let Some(long_vowels) = args.long_vowels {
+        builder = builder.long_vowels(long_vowels);
+    }
+    if let Some(word_separator) = args.word_separator {
+        builder = builder.word_separator(word_separator);
+    }
+    if let Some(bytes) = args.max_length {
+        builder = builder.max_length(bytes);
+    }
+    if let Some(mode) = args.sanitize {
+        builder = builder.sanitize(mode);
+    }
+    if let Some(encoding) = args.from_encoding {
+        builder = builder.from_encoding(encoding);
+    }
+    builder.build()
+}
+
 fn main() -> Result<()> {
-    let args = cli::Args::parse();
-
-    match args.separator {
-        '/' | '\0' => bail!("invalid separator: cannot use '/' or null character"),
-        '.' => bail!("invalid separator: '.' would break file extension parsing"),
-        _ => {}
-    }
-
-    let paths: Vec<PathBuf> = if !args.files.is_empty() {
-        args.files
-    } else if !io::stdin().is_terminal() {
-        io::stdin()
-            .lock()
-            .lines()
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .filter(|l| !l.is_empty())
-            .map(PathBuf::from)
-            .collect()
-    } else {
-        cli::Args::parse_from(["romv", "--help"]);
-        unreachable!();
+    let matches = cli::Args::command().get_matches();
+    let mut args = cli::Args::from_arg_matches(&matches)?;
+
+    match &args.command {
+        Some(cli::Command::Undo(undo_args)) => return undo(undo_args),
+        Some(cli::Command::Config(cli::ConfigCommand::Show { profile })) => {
+            return show_config(profile.as_deref());
+        }
+        None => {}
+    }
+    config::load(args.profile.as_deref())?
+        .settings
+        .apply(&mut args, &matches);
+    validate(&mut args, &matches);
+
+    let converter = converter(&args)?;
+    let mut plan = match &args.plan {
+        Some(path) => RenamePlan::from_entries(format::read_plan(path)?),
+        None => {
+            let paths: Vec<PathBuf> = if !args.files.is_empty() {
+                args.files
+            } else if args.null || !io::stdin().is_terminal() {
+                // Raw bytes, so names that are not UTF-8 can be piped in too
+                let mut input = Vec::new();
+                io::stdin().lock().read_to_end(&mut input)?;
+                let delimiter = if args.null { b'\0' } else { b'\n' };
+                input
+                    .split(|&b| b == delimiter)
+                    .map(|p| {
+                        if args.null {
+                            p
+                        } else {
+                            p.strip_suffix(b"\r").unwrap_or(p)
+                        }
+                    })
+                    .filter(|p| !p.is_empty())
+                    .map(|p| PathBuf::from(OsStr::from_bytes(p)))
+                    .collect()
+            } else {
+                cli::Args::parse_from(["romv", "--help"]);
+                unreachable!();
+            };
+
+            let paths = if args.recursive {
+                walk::expand(&paths, args.max_depth)
+            } else {
+                paths
+            };
+            let paths = if args.full_path {
+                walk::with_parents(&paths, |dir| {
+                    dir.file_name().is_some_and(|name| {
+                        converter
+                            .decode(name)
+                            .is_some_and(|decoded| converter.convert(&decoded).as_str() != name)
+                    })
+                })
+            } else {
+                paths
+            };
+
+            let mut filter = Filter::default();
+            for pattern in &args.include {
+                filter = filter.include(pattern)?;
+            }
+            for pattern in &args.exclude {
+                filter = filter.exclude(pattern)?;
+            }
+            if let Some(regex) = &args.pattern {
+                filter = filter.regex(regex)?;
+            }
+            if let Some(file_type) = args.file_type {
+                filter = filter.file_type(file_type);
+            }
+
+            RenamePlan::build_filtered(&paths, &converter, &filter)
+        }
     };
 
-    let plan = plan::RenamePlan::build(&paths, args.separator, args.verbose);
+    plan.resolve_conflicts(args.on_conflict, &converter);
+    report::skips(&plan, args.verbose);
+    let collisions = plan.check_collisions();
+    report::collisions(&collisions);
+    let non_ascii = if args.strict_ascii {
+        plan.check_ascii()
+    } else {
+        Vec::new()
+    };
+    report::non_ascii(&non_ascii);
+    if args.format != format::Format::Human {
+        format::write_plan(&plan, args.format, &mut io::stdout().lock())?;
+    }
 
     if plan.entries.is_empty() {
-        if plan.skipped > 0 {
-            eprintln!("Nothing to rename ({} skipped).", plan.skipped);
-        } else {
-            eprintln!("Nothing to rename.");
+        match (plan.skipped.len(), plan.filtered.len()) {
+            (0, 0) => eprintln!("Nothing to rename."),
+            (skipped, 0) => eprintln!("Nothing to rename ({skipped} skipped)."),
+            (0, filtered) => eprintln!("Nothing to rename ({filtered} filtered)."),
+            (skipped, filtered) => {
+                eprintln!("Nothing to rename ({skipped} skipped, {filtered} filtered).")
+            }
         }
         return Ok(());
     }
 
-    if plan.check_collisions() {
+    if !collisions.is_empty() {
         bail!("Aborting due to collisions. No files were renamed.");
     }
+    if !non_ascii.is_empty() {
+        bail!("Aborting due to non-ASCII targets. No files were renamed.");
+    }
 
     let execute = args.yes || args.interactive;
-    plan.display(execute);
+    if args.format == format::Format::Human && !args.print0 {
+        report::plan(&plan, execute);
+    }
 
     if !execute {
         eprintln!(
@@ -62,9 +288,6 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let mut renamed = 0;
-    let mut errors = 0;
-
     // Read interactive confirmations from /dev/tty so that piped stdin
     // (e.g. `ls | romv -i`) does not conflict with user input.
     let mut tty_reader = if args.interactive {
@@ -75,9 +298,10 @@ fn main() -> Result<()> {
         None
     };
 
+    let mut accepted = Vec::with_capacity(plan.entries.len());
     for entry in &plan.entries {
         if entry.status != EntryStatus::Ready {
-            errors += 1;
+            accepted.push(false);
             continue;
         }
 
@@ -102,31 +326,53 @@ fn main() -> Result<()> {
                         entry.source.display()
                     );
                 }
+                accepted.push(false);
                 continue;
             }
         }
+        accepted.push(true);
+    }
 
-        match rename::safe_rename(&entry.source, &entry.target) {
-            Ok(()) => {
-                renamed += 1;
-            }
-            Err(e) => {
-                eprintln!(
-                    "{} {} -> {}: {}",
-                    "error:".red().bold(),
-                    entry.source.display(),
-                    entry.target.display(),
-                    e
-                );
-                errors += 1;
-            }
+    let mut journal = match journal::default_path().and_then(journal::Journal::new) {
+        Ok(journal) => Some(journal),
+        Err(e) => {
+            eprintln!("{} renames will not be journaled: {e}", "warning:".yellow());
+            None
+        }
+    };
+
+    let romv::Summary {
+        renamed,
+        targets,
+        failures,
+        errors,
+    } = plan.execute(&accepted, |source, target| {
+        if let Some(journal) = &mut journal
+            && let Err(e) = journal.append(source, target)
+        {
+            eprintln!(
+                "{} cannot write journal for {}: {e}",
+                "warning:".yellow(),
+                source.display()
+            );
+        }
+    });
+    report::failures(&failures);
+
+    if args.print0 {
+        let mut out = io::stdout().lock();
+        for target in &targets {
+            out.write_all(target.as_os_str().as_bytes())?;
+            out.write_all(b"\0")?;
         }
+        out.flush()?;
     }
 
     if args.verbose || errors > 0 {
         eprintln!(
-            "Done: {renamed} renamed, {errors} errors, {} skipped.",
-            plan.skipped
+            "Done: {renamed} renamed, {errors} errors, {} skipped, {} filtered.",
+            plan.skipped.len(),
+            plan.filtered.len()
         );
     }
 
@@ -0,0 +1,19 @@
+use clap::ValueEnum;
+use unicode_normalization::UnicodeNormalization;
+
+/// Unicode normalization form of converted names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Form {
+    /// Composed, as used by Linux and Windows (`が` is one code point)
+    #[default]
+    Nfc,
+    /// Decomposed, as stored by macOS (`が` is `か` + U+3099)
+    Nfd,
+}
+
+pub fn normalize(s: &str, form: Form) -> String {
+    match form {
+        Form::Nfc => s.nfc().collect(),
+        Form::Nfd => s.nfd().collect(),
+    }
+}
@@ -1,144 +1,488 @@
-use colored::Colorize;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use clap::ValueEnum;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 
-use crate::convert;
+use crate::convert::Converter;
+use crate::filter::Filter;
+use crate::rename;
+use crate::sanitize::Fix;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EntryStatus {
     Ready,
     TargetExists,
+    /// Another entry in the batch renames to the same target
+    Collision,
+}
+
+impl EntryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryStatus::Ready => "ready",
+            EntryStatus::TargetExists => "target_exists",
+            EntryStatus::Collision => "collision",
+        }
+    }
+}
+
+/// Why a path did not make it into the plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    NotFound,
+    InvalidName,
+    EmptyName,
+    Unchanged,
+    /// Dropped by `--on-conflict skip`
+    Conflict,
+    /// Longer than the name length limit even after truncation
+    TooLong {
+        /// Length of the converted name in bytes
+        length: usize,
+        limit: usize,
+    },
+}
+
+impl SkipReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SkipReason::NotFound => "not_found",
+            SkipReason::InvalidName => "invalid_name",
+            SkipReason::EmptyName => "empty_name",
+            SkipReason::Unchanged => "unchanged",
+            SkipReason::Conflict => "conflict",
+            SkipReason::TooLong { .. } => "too_long",
+        }
+    }
 }
 
 #[derive(Debug)]
+pub struct SkippedEntry {
+    pub source: PathBuf,
+    pub reason: SkipReason,
+    /// The target the path was skipped for, with [`SkipReason::Conflict`]
+    pub target: Option<PathBuf>,
+}
+
+/// What to do when a target already exists or is claimed by another
+/// entry of the batch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OnConflict {
+    /// Abort the batch on collisions; existing targets are errors
+    #[default]
+    Abort,
+    /// Leave conflicting files alone
+    Skip,
+    /// Append `_1`, `_2`, ... to the stem until the name is free
+    Suffix,
+    /// Move an existing target aside to `NAME~` before renaming
+    OverwriteBackup,
+}
+
+#[derive(Debug, Clone)]
 pub struct RenameEntry {
     pub source: PathBuf,
     pub target: PathBuf,
     pub status: EntryStatus,
+    /// Where an existing `target` is moved before the rename
+    pub backup: Option<PathBuf>,
+    /// Changes made to the converted name by sanitizing and truncation
+    pub fixes: Vec<Fix>,
 }
 
 pub struct RenamePlan {
     pub entries: Vec<RenameEntry>,
-    pub skipped: usize,
+    pub skipped: Vec<SkippedEntry>,
+    /// Paths left out by the [`Filter`], kept apart from `skipped`
+    pub filtered: Vec<PathBuf>,
+}
+
+fn exists(path: &Path) -> bool {
+    path.exists() || path.symlink_metadata().is_ok()
+}
+
+/// Recompute entry statuses against the disk and the batch itself.
+///
+/// An existing target is not a problem if it is the source of another
+/// ready entry that is renamed away in the same batch, so chains
+/// (`A → B`, `B → C`) and cycles (`A ↔ B`) are valid as a whole. Entries
+/// with a backup move their existing target aside and are always ready.
+pub fn update_statuses(entries: &mut [RenameEntry]) {
+    let statuses: Vec<EntryStatus> = {
+        let sources: HashMap<&Path, usize> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.source.as_path(), i))
+            .collect();
+        let blocked: Vec<bool> = entries
+            .iter()
+            .map(|e| {
+                e.backup.is_none()
+                    && exists(&e.target)
+                    && !rename::is_case_change(&e.source, &e.target)
+            })
+            .collect();
+
+        // Start optimistic and demote entries whose target stays occupied,
+        // until nothing changes. Cycles keep each other ready.
+        let mut ready: Vec<bool> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| !blocked[i] || sources.contains_key(e.target.as_path()))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, entry) in entries.iter().enumerate() {
+                if !ready[i] || !blocked[i] {
+                    continue;
+                }
+                let vacated = sources
+                    .get(entry.target.as_path())
+                    .is_some_and(|&j| j != i && ready[j]);
+                if !vacated {
+                    ready[i] = false;
+                    changed = true;
+                }
+            }
+        }
+
+        ready
+            .into_iter()
+            .map(|r| {
+                if r {
+                    EntryStatus::Ready
+                } else {
+                    EntryStatus::TargetExists
+                }
+            })
+            .collect()
+    };
+
+    for (entry, status) in entries.iter_mut().zip(statuses) {
+        entry.status = status;
+    }
+}
+
+/// First free backup name for `target`: `NAME~`, then `NAME.~1~`,
+/// `NAME.~2~`, ... (the same scheme as GNU `mv --backup`).
+fn backup_path(target: &Path, taken: &HashSet<PathBuf>) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    std::iter::once(format!("{name}~"))
+        .chain((1..).map(|n| format!("{name}.~{n}~")))
+        .map(|backup| target.with_file_name(backup))
+        .find(|backup| !taken.contains(backup) && !exists(backup))
+        .unwrap_or_default()
+}
+
+/// Order `items` deepest path first, keeping the input order otherwise.
+fn by_depth<T>(items: impl IntoIterator<Item = T>, path: impl Fn(&T) -> &Path) -> Vec<T> {
+    let mut items: Vec<T> = items.into_iter().collect();
+    items.sort_by_key(|item| {
+        Reverse(
+            path(item)
+                .components()
+                .filter(|c| *c != Component::CurDir)
+                .count(),
+        )
+    });
+    items
 }
 
 impl RenamePlan {
-    pub fn build(paths: &[PathBuf], separator: char, verbose: bool) -> Self {
+    /// Plan renames for `paths`. Entries are ordered deepest-first, so
+    /// the contents of a directory are renamed before the directory.
+    ///
+    /// Names longer than [`Converter::max_length`], or than the limit of
+    /// the filesystem if it is not set, are truncated with
+    /// [`Converter::truncate`] or skipped as [`SkipReason::TooLong`].
+    pub fn build(paths: &[PathBuf], converter: &Converter) -> Self {
+        Self::build_filtered(paths, converter, &Filter::default())
+    }
+
+    /// Like [`RenamePlan::build`], leaving out the paths `filter` does
+    /// not accept before converting them.
+    pub fn build_filtered(paths: &[PathBuf], converter: &Converter, filter: &Filter) -> Self {
         let mut entries = Vec::new();
-        let mut skipped = 0;
+        let mut filtered = Vec::new();
+        let mut skipped = Vec::new();
+        let mut skip = |source: &PathBuf, reason| {
+            skipped.push(SkippedEntry {
+                source: source.clone(),
+                reason,
+                target: None,
+            })
+        };
 
-        for source in paths {
-            if source.symlink_metadata().is_err() {
-                eprintln!("{} {} (not found)", "skip:".yellow(), source.display());
-                skipped += 1;
+        let mut name_max = HashMap::new();
+
+        for source in by_depth(paths, |p| p.as_path()) {
+            let Ok(metadata) = source.symlink_metadata() else {
+                skip(source, SkipReason::NotFound);
+                continue;
+            };
+            if !filter.accepts(source, &metadata) {
+                filtered.push(source.clone());
                 continue;
             }
 
-            let filename = match source.file_name().and_then(|f| f.to_str()) {
-                Some(f) => f.to_string(),
-                None => {
-                    eprintln!(
-                        "{} cannot extract filename: {}",
-                        "skip:".yellow(),
-                        source.display()
-                    );
-                    skipped += 1;
-                    continue;
-                }
+            let original = source.file_name().unwrap_or_default();
+            let Some(filename) = source.file_name().and_then(|f| converter.decode(f)) else {
+                skip(source, SkipReason::InvalidName);
+                continue;
             };
 
-            let converted = convert::convert_filename(&filename, separator);
+            let (converted, mut fixes) = converter.convert_with_fixes(&filename);
 
             if converted.is_empty() {
-                eprintln!(
-                    "{} conversion produced empty name: {}",
-                    "skip:".yellow(),
-                    source.display()
-                );
-                skipped += 1;
+                skip(source, SkipReason::EmptyName);
                 continue;
             }
 
-            if converted == filename {
-                if verbose {
-                    eprintln!("{} {} (unchanged)", "skip:".dimmed(), source.display());
-                }
-                skipped += 1;
+            let parent = source.parent().unwrap_or(Path::new(""));
+            let limit = converter.max_length().unwrap_or_else(|| {
+                *name_max
+                    .entry(parent.to_path_buf())
+                    .or_insert_with(|| rename::name_max(parent))
+            });
+            let length = converted.len();
+            let converted = if length <= limit {
+                converted
+            } else if let Some(truncated) = converter.truncate(&converted, limit) {
+                fixes.push(Fix::Truncated { length, limit });
+                truncated
+            } else {
+                skip(source, SkipReason::TooLong { length, limit });
+                continue;
+            };
+
+            // A decoded legacy name changes even if its text does not
+            if converted.as_str() == original {
+                skip(source, SkipReason::Unchanged);
                 continue;
             }
 
             let target = source.with_file_name(&converted);
-            let status = if target.exists() || target.symlink_metadata().is_ok() {
-                EntryStatus::TargetExists
-            } else {
-                EntryStatus::Ready
-            };
 
             entries.push(RenameEntry {
                 source: source.clone(),
                 target,
-                status,
+                status: EntryStatus::Ready,
+                backup: None,
+                fixes,
             });
         }
 
-        RenamePlan { entries, skipped }
+        update_statuses(&mut entries);
+        RenamePlan {
+            entries,
+            skipped,
+            filtered,
+        }
     }
 
-    /// Check if multiple sources would rename to the same target.
-    /// Returns true if collisions were found.
-    pub fn check_collisions(&self) -> bool {
-        let mut target_map: HashMap<&PathBuf, Vec<&PathBuf>> = HashMap::new();
-        for entry in &self.entries {
-            target_map
-                .entry(&entry.target)
-                .or_default()
-                .push(&entry.source);
+    /// Plan previously exported (and possibly edited) `(source, target)`
+    /// pairs. Statuses are recomputed against the current disk state.
+    pub fn from_pairs(pairs: Vec<(PathBuf, PathBuf)>) -> Self {
+        Self::from_entries(
+            pairs
+                .into_iter()
+                .map(|(source, target)| RenameEntry {
+                    source,
+                    target,
+                    status: EntryStatus::Ready,
+                    backup: None,
+                    fixes: Vec::new(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Plan previously exported (and possibly edited) entries. Statuses
+    /// are recomputed against the current disk state, and a `backup` is
+    /// only kept for a target that is still in the way.
+    pub fn from_entries(planned: Vec<RenameEntry>) -> Self {
+        let mut entries = Vec::new();
+        let mut backups = Vec::new();
+        let mut skipped = Vec::new();
+
+        for entry in by_depth(planned, |entry| entry.source.as_path()) {
+            let source = entry.source;
+            if source.symlink_metadata().is_err() {
+                skipped.push(SkippedEntry {
+                    source,
+                    reason: SkipReason::NotFound,
+                    target: None,
+                });
+                continue;
+            }
+            if source == entry.target {
+                skipped.push(SkippedEntry {
+                    source,
+                    reason: SkipReason::Unchanged,
+                    target: None,
+                });
+                continue;
+            }
+            backups.push(entry.backup);
+            entries.push(RenameEntry {
+                source,
+                target: entry.target,
+                status: EntryStatus::Ready,
+                backup: None,
+                fixes: Vec::new(),
+            });
+        }
+
+        update_statuses(&mut entries);
+        for (entry, backup) in entries.iter_mut().zip(backups) {
+            if entry.status == EntryStatus::TargetExists {
+                entry.backup = backup;
+            }
+        }
+        update_statuses(&mut entries);
+        RenamePlan {
+            entries,
+            skipped,
+            filtered: Vec::new(),
+        }
+    }
+
+    /// Resolve entries whose target exists on disk or is already claimed
+    /// by an earlier entry, according to `strategy`. The first entry
+    /// claiming a name keeps it. New names are checked against the disk
+    /// and every target of the batch, so the result is deterministic.
+    /// [`OnConflict::Suffix`] numbers names with [`Converter::with_suffix`],
+    /// within the same length limit as [`Self::build`], and skips an entry
+    /// as [`SkipReason::TooLong`] if no numbered name fits.
+    ///
+    /// With [`OnConflict::Abort`] nothing changes; with
+    /// [`OnConflict::OverwriteBackup`] only existing targets are resolved
+    /// and collisions within the batch are left to [`Self::check_collisions`].
+    pub fn resolve_conflicts(&mut self, strategy: OnConflict, converter: &Converter) {
+        if strategy == OnConflict::Abort {
+            return;
         }
-        let mut found = false;
-        for (target, sources) in &target_map {
-            if sources.len() > 1 {
-                eprintln!(
-                    "{} multiple files would rename to {}:",
-                    "collision:".red().bold(),
-                    target.display()
-                );
-                for s in sources {
-                    eprintln!("  - {}", s.display());
+
+        let reserved: HashSet<PathBuf> = self.entries.iter().map(|e| e.target.clone()).collect();
+        let mut claimed: HashSet<PathBuf> = HashSet::new();
+        let mut kept = Vec::new();
+        let mut name_max = HashMap::new();
+
+        for mut entry in std::mem::take(&mut self.entries) {
+            let collides = claimed.contains(&entry.target);
+            if entry.status == EntryStatus::Ready && !collides {
+                claimed.insert(entry.target.clone());
+                kept.push(entry);
+                continue;
+            }
+
+            match strategy {
+                OnConflict::Abort => unreachable!(),
+                OnConflict::Skip => {
+                    self.skipped.push(SkippedEntry {
+                        source: entry.source,
+                        reason: SkipReason::Conflict,
+                        target: Some(entry.target),
+                    });
+                    continue;
+                }
+                OnConflict::Suffix => {
+                    let name = entry
+                        .target
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned();
+                    let parent = entry.target.parent().unwrap_or(Path::new(""));
+                    let limit = converter.max_length().unwrap_or_else(|| {
+                        *name_max
+                            .entry(parent.to_path_buf())
+                            .or_insert_with(|| rename::name_max(parent))
+                    });
+                    let free = (1..)
+                        .map_while(|n| Some((n, converter.with_suffix(&name, n, limit)?)))
+                        .map(|(n, name)| (n, entry.target.with_file_name(name)))
+                        .find(|(_, t)| !reserved.contains(t) && !claimed.contains(t) && !exists(t));
+                    let Some((n, free)) = free else {
+                        self.skipped.push(SkippedEntry {
+                            source: entry.source,
+                            reason: SkipReason::TooLong {
+                                length: name.len() + "_1".len(),
+                                limit,
+                            },
+                            target: None,
+                        });
+                        continue;
+                    };
+                    let length = name.len() + format!("_{n}").len();
+                    if free.file_name().is_some_and(|f| f.len() < length) {
+                        entry.fixes.push(Fix::Truncated { length, limit });
+                    }
+                    entry.target = free;
+                    entry.status = EntryStatus::Ready;
+                }
+                OnConflict::OverwriteBackup => {
+                    if !collides {
+                        let backup = backup_path(&entry.target, &claimed);
+                        claimed.insert(backup.clone());
+                        entry.backup = Some(backup);
+                        entry.status = EntryStatus::Ready;
+                    }
                 }
-                found = true;
             }
+            claimed.insert(entry.target.clone());
+            kept.push(entry);
         }
-        found
+
+        // Skipped entries no longer vacate their source
+        update_statuses(&mut kept);
+        self.entries = kept;
     }
 
-    pub fn display(&self, execute: bool) {
+    /// Find targets that multiple sources would rename to, and mark the
+    /// entries involved as [`EntryStatus::Collision`]. Returns each such
+    /// target with its sources, in plan order.
+    pub fn check_collisions(&mut self) -> Vec<(PathBuf, Vec<PathBuf>)> {
+        let mut collisions: Vec<(PathBuf, Vec<PathBuf>)> = Vec::new();
+        let mut index: HashMap<&Path, usize> = HashMap::new();
         for entry in &self.entries {
-            let target_name = entry
+            let i = *index.entry(&entry.target).or_insert_with(|| {
+                collisions.push((entry.target.clone(), Vec::new()));
+                collisions.len() - 1
+            });
+            collisions[i].1.push(entry.source.clone());
+        }
+        collisions.retain(|(_, sources)| sources.len() > 1);
+
+        for entry in &mut self.entries {
+            if collisions.iter().any(|(target, _)| *target == entry.target) {
+                entry.status = EntryStatus::Collision;
+            }
+        }
+        collisions
+    }
+
+    /// Find the non-ASCII characters left in the new file names. Returns
+    /// each target that has any, with its distinct non-ASCII characters.
+    pub fn check_ascii(&self) -> Vec<(PathBuf, Vec<char>)> {
+        let mut found = Vec::new();
+        for entry in &self.entries {
+            let name = entry
                 .target
                 .file_name()
                 .unwrap_or_default()
                 .to_string_lossy();
-
-            match entry.status {
-                EntryStatus::TargetExists => {
-                    eprintln!(
-                        "{} {} {} {}",
-                        entry.source.display(),
-                        "->".bold(),
-                        target_name.red(),
-                        "(already exists)".red(),
-                    );
-                }
-                EntryStatus::Ready => {
-                    println!(
-                        "{} {} {} {}",
-                        entry.source.display(),
-                        "->".bold(),
-                        target_name.green(),
-                        if execute { "" } else { "(dry-run)" }
-                    );
+            let mut chars: Vec<char> = Vec::new();
+            for c in name.chars().filter(|c| !c.is_ascii()) {
+                if !chars.contains(&c) {
+                    chars.push(c);
                 }
             }
+            if !chars.is_empty() {
+                found.push((entry.target.clone(), chars));
+            }
         }
+        found
     }
 }
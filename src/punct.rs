@@ -0,0 +1,128 @@
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+
+/// Default replacements for Japanese punctuation and symbols. A space
+/// becomes the separator like any other space in the name.
+pub const DEFAULT_MAPPINGS: &[(char, &str)] = &[
+    ('〜', "~"),
+    ('、', ","),
+    ('。', ""),
+    ('・', " "),
+    ('【', "["),
+    ('】', "]"),
+    ('〔', "["),
+    ('〕', "]"),
+    ('〖', "["),
+    ('〗', "]"),
+    ('「', "["),
+    ('」', "]"),
+    ('『', "["),
+    ('』', "]"),
+    ('《', "("),
+    ('》', ")"),
+    ('〈', "("),
+    ('〉', ")"),
+    ('※', ""),
+    ('♪', ""),
+    ('★', ""),
+    ('☆', ""),
+    ('→', "-"),
+    ('←', "-"),
+    ('…', ""),
+    ('‥', ""),
+    ('‐', "-"),
+    ('–', "-"),
+    ('—', "-"),
+    ('―', "-"),
+    ('“', ""),
+    ('”', ""),
+    ('‘', ""),
+    ('’', ""),
+    ('×', "x"),
+];
+
+/// Replacements for punctuation and symbols, applied before
+/// romanization. Starts from [`DEFAULT_MAPPINGS`].
+///
+/// ```
+/// use romv::Punctuation;
+///
+/// let mut punctuation = Punctuation::default();
+/// assert_eq!(punctuation.apply("【速報】"), "[速報]");
+/// punctuation.insert('【', "(").unwrap();
+/// punctuation.insert('】', "").unwrap();
+/// assert_eq!(punctuation.apply("【速報】"), "(速報");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Punctuation {
+    map: HashMap<char, String>,
+}
+
+impl Default for Punctuation {
+    fn default() -> Self {
+        Punctuation {
+            map: DEFAULT_MAPPINGS
+                .iter()
+                .map(|&(from, to)| (from, to.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Punctuation {
+    /// Replace `from` with `to` (which may be empty to remove it).
+    /// Fails if `to` contains `/` or NUL.
+    pub fn insert(&mut self, from: char, to: &str) -> Result<()> {
+        if to.contains(['/', '\0']) {
+            bail!("invalid mapping for {from:?}: {to:?} contains '/' or NUL");
+        }
+        self.map.insert(from, to.to_string());
+        Ok(())
+    }
+
+    pub fn apply(&self, s: &str) -> String {
+        let mut out = String::with_capacity(s.len());
+        for c in s.chars() {
+            match self.map.get(&c) {
+                Some(to) => out.push_str(to),
+                None => out.push(c),
+            }
+        }
+        out
+    }
+}
+
+/// Parse a `FROM=TO` mapping, where `FROM` is a single character.
+pub fn parse_mapping(s: &str) -> Result<(char, String)> {
+    let mut chars = s.chars();
+    let Some(from) = chars.next() else {
+        bail!("expected FROM=TO, got an empty mapping");
+    };
+    match chars.as_str().strip_prefix('=') {
+        Some(to) => Ok((from, to.to_string())),
+        None => bail!("expected FROM=TO with a single character FROM, got {s:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mappings() {
+        assert_eq!(parse_mapping("【=[").unwrap(), ('【', "[".to_string()));
+        assert_eq!(parse_mapping("※=").unwrap(), ('※', String::new()));
+        assert_eq!(parse_mapping("==eq").unwrap(), ('=', "eq".to_string()));
+        assert!(parse_mapping("【】=[]").is_err());
+        assert!(parse_mapping("【").is_err());
+    }
+
+    #[test]
+    fn override_and_reject_slash() {
+        let mut punctuation = Punctuation::default();
+        punctuation.insert('・', "-").unwrap();
+        punctuation.insert('♡', "love").unwrap();
+        assert_eq!(punctuation.apply("a・b♡"), "a-blove");
+        assert!(punctuation.insert('→', "/").is_err());
+    }
+}
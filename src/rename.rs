@@ -1,22 +1,327 @@
-use anyhow::{Result, bail};
-use std::path::Path;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 
-/// Rename `source` to `target` with no-clobber safety.
+/// Why a rename failed.
+#[derive(Debug)]
+pub enum RenameError {
+    /// `source` does not exist
+    SourceNotFound(PathBuf),
+    /// `target` already exists (possibly created after the plan was checked)
+    TargetExists(PathBuf),
+    /// `source` and `target` are on different filesystems
+    CrossDevice,
+    /// The directory of `source` or `target` is not writable
+    PermissionDenied(io::Error),
+    /// Any other I/O error
+    Io(io::Error),
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SourceNotFound(path) => write!(f, "source does not exist: {}", path.display()),
+            Self::TargetExists(path) => write!(f, "target already exists: {}", path.display()),
+            Self::CrossDevice => write!(f, "source and target are on different filesystems"),
+            Self::PermissionDenied(e) => write!(f, "permission denied: {e}"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::PermissionDenied(e) | Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl RenameError {
+    fn from_io(e: io::Error, source: &Path, target: &Path) -> Self {
+        match e.kind() {
+            io::ErrorKind::AlreadyExists | io::ErrorKind::DirectoryNotEmpty => {
+                Self::TargetExists(target.to_path_buf())
+            }
+            io::ErrorKind::NotFound if source.symlink_metadata().is_err() => {
+                Self::SourceNotFound(source.to_path_buf())
+            }
+            io::ErrorKind::CrossesDevices => Self::CrossDevice,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(e),
+            _ => Self::Io(e),
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Called right before the rename itself, after the existence checks,
+    /// so tests can create the target in the race window.
+    static BEFORE_RENAME: std::cell::RefCell<Option<Box<dyn Fn()>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+fn before_rename() {
+    #[cfg(test)]
+    BEFORE_RENAME.with(|hook| {
+        if let Some(hook) = hook.borrow().as_ref() {
+            hook();
+        }
+    });
+}
+
+/// Whether the filesystem or kernel cannot perform the requested
+/// operation at all, as opposed to failing for this particular file.
+/// `EPERM` is a real permission error (e.g. a sticky directory), so it
+/// is not a reason to fall back to a less safe method.
+fn unsupported(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::EINVAL | libc::ENOSYS | libc::EOPNOTSUPP)
+    )
+}
+
+/// Atomic rename that fails with `EEXIST` instead of replacing `target`.
+#[cfg(target_os = "linux")]
+fn rename_noreplace(source: &Path, target: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let source = CString::new(source.as_os_str().as_bytes())?;
+    let target = CString::new(target.as_os_str().as_bytes())?;
+    // SAFETY: both paths are valid NUL-terminated strings that outlive the call
+    let ret = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            source.as_ptr(),
+            libc::AT_FDCWD,
+            target.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rename_noreplace(_source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::Error::from_raw_os_error(libc::ENOSYS))
+}
+
+/// No-clobber rename for files: `link` fails atomically if `target`
+/// exists. Directories cannot be hard-linked.
+fn link_unlink(source: &Path, target: &Path) -> io::Result<()> {
+    std::fs::hard_link(source, target)?;
+    std::fs::remove_file(source)
+}
+
+/// Last resort: check, then rename. Leaves a small race window.
+fn check_then_rename(source: &Path, target: &Path) -> io::Result<()> {
+    if target.symlink_metadata().is_ok() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    std::fs::rename(source, target)
+}
+
+fn rename_no_clobber(source: &Path, target: &Path) -> io::Result<()> {
+    match rename_noreplace(source, target) {
+        Err(e) if unsupported(&e) => match link_unlink(source, target) {
+            // `link` fails with EPERM for directories and on filesystems
+            // without hard links; the rename itself still reports real
+            // permission errors
+            Err(e) if unsupported(&e) || e.raw_os_error() == Some(libc::EPERM) => {
+                check_then_rename(source, target)
+            }
+            result => result,
+        },
+        result => result,
+    }
+}
+
+/// Name length limit assumed when the filesystem does not report one.
+const DEFAULT_NAME_MAX: usize = 255;
+
+/// Longest file name in bytes that the filesystem of `dir` accepts
+/// (`pathconf(_PC_NAME_MAX)`, e.g. 143 on ecryptfs).
+pub fn name_max(dir: &Path) -> usize {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(dir) = CString::new(dir.as_os_str().as_bytes()) else {
+        return DEFAULT_NAME_MAX;
+    };
+    // SAFETY: `dir` is a valid NUL-terminated string that outlives the call
+    let max = unsafe { libc::pathconf(dir.as_ptr(), libc::_PC_NAME_MAX) };
+    usize::try_from(max)
+        .ok()
+        .filter(|&max| max > 0)
+        .unwrap_or(DEFAULT_NAME_MAX)
+}
+
+/// Free temporary name next to `path`, used to move it out of the way.
+pub fn temp_name(path: &Path, n: usize) -> PathBuf {
+    (0..)
+        .map(|i| path.with_file_name(format!(".romv-{}-{n}-{i}.tmp", std::process::id())))
+        .find(|p| p.symlink_metadata().is_err())
+        .unwrap_or_default()
+}
+
+/// Whether renaming `source` to `target` only changes the case of the
+/// file name and both already refer to the same file, as they do on a
+/// case-insensitive filesystem (`Readme.txt` → `readme.txt` on vfat).
+pub fn is_case_change(source: &Path, target: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let (Some(a), Some(b)) = (source.file_name(), target.file_name()) else {
+        return false;
+    };
+    if a == b
+        || source.parent() != target.parent()
+        || a.to_string_lossy().to_lowercase() != b.to_string_lossy().to_lowercase()
+    {
+        return false;
+    }
+    match (source.symlink_metadata(), target.symlink_metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Case-only rename in two steps, since a case-insensitive filesystem
+/// sees `target` as already existing. If `target` is still there after
+/// moving `source` aside, it was a separate hard link after all, and
+/// `source` is put back.
+fn rename_case(source: &Path, target: &Path) -> Result<(), RenameError> {
+    let temp = temp_name(source, 0);
+    rename_no_clobber(source, &temp).map_err(|e| RenameError::from_io(e, source, &temp))?;
+    before_rename();
+    rename_no_clobber(&temp, target).map_err(|e| {
+        let _ = rename_no_clobber(&temp, source);
+        RenameError::from_io(e, source, target)
+    })
+}
+
+/// Rename `source` to `target` without ever replacing an existing
+/// `target`.
 ///
-/// Returns an error if:
-/// - `source` does not exist
-/// - `target` already exists
-pub fn safe_rename(source: &Path, target: &Path) -> Result<()> {
+/// Uses `renameat2(RENAME_NOREPLACE)` on Linux. Where the kernel or
+/// filesystem rejects it, files fall back to `link` + `unlink`, and
+/// anything else to checking for `target` before renaming. Case-only
+/// renames of the same file go through a temporary name.
+pub fn safe_rename(source: &Path, target: &Path) -> Result<(), RenameError> {
     if source.symlink_metadata().is_err() {
-        bail!("source does not exist: {}", source.display());
+        return Err(RenameError::SourceNotFound(source.to_path_buf()));
+    }
+    if target.symlink_metadata().is_ok() {
+        if is_case_change(source, target) {
+            return rename_case(source, target);
+        }
+        return Err(RenameError::TargetExists(target.to_path_buf()));
+    }
+    before_rename();
+
+    rename_no_clobber(source, target).map_err(|e| RenameError::from_io(e, source, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn set_hook(hook: impl Fn() + 'static) {
+        BEFORE_RENAME.with(|h| *h.borrow_mut() = Some(Box::new(hook)));
+    }
+
+    #[test]
+    fn target_created_in_race_window_is_not_clobbered() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        fs::write(&source, "source").unwrap();
+
+        let racer = target.clone();
+        set_hook(move || fs::write(&racer, "racer").unwrap());
+        let result = safe_rename(&source, &target);
+
+        assert!(matches!(result, Err(RenameError::TargetExists(_))));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "racer");
+        assert_eq!(fs::read_to_string(&source).unwrap(), "source");
     }
-    if target.exists() || target.symlink_metadata().is_ok() {
-        bail!(
-            "target already exists: {} -> {}",
-            source.display(),
-            target.display()
-        );
+
+    #[test]
+    fn link_unlink_does_not_clobber() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        fs::write(&source, "source").unwrap();
+        fs::write(&target, "target").unwrap();
+
+        let err = link_unlink(&source, &target).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "target");
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn case_change_needs_same_file_and_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let upper = dir.path().join("Readme.txt");
+        let lower = dir.path().join("readme.txt");
+        let other = dir.path().join("other.txt");
+        fs::write(&upper, "").unwrap();
+        fs::hard_link(&upper, &lower).unwrap();
+        fs::hard_link(&upper, &other).unwrap();
+
+        assert!(is_case_change(&upper, &lower));
+        assert!(!is_case_change(&upper, &other));
+        assert!(!is_case_change(&upper, &upper));
+    }
+
+    #[test]
+    fn case_change_restores_source_if_target_is_separate() {
+        // On a case-sensitive filesystem, hard links look like a case-only
+        // rename until the first step shows the target is its own entry
+        let dir = tempfile::tempdir().unwrap();
+        let upper = dir.path().join("Readme.txt");
+        let lower = dir.path().join("readme.txt");
+        fs::write(&upper, "").unwrap();
+        fs::hard_link(&upper, &lower).unwrap();
+
+        let result = safe_rename(&upper, &lower);
+
+        assert!(matches!(result, Err(RenameError::TargetExists(_))));
+        assert!(upper.exists() && lower.exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn eperm_is_a_permission_error() {
+        let eperm = io::Error::from_raw_os_error(libc::EPERM);
+        assert!(!unsupported(&eperm));
+        assert!(unsupported(&io::Error::from_raw_os_error(libc::EINVAL)));
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::write(&source, "").unwrap();
+        assert!(matches!(
+            RenameError::from_io(eperm, &source, &dir.path().join("target")),
+            RenameError::PermissionDenied(_)
+        ));
+    }
+
+    #[test]
+    fn missing_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = safe_rename(&dir.path().join("missing"), &dir.path().join("target"));
+        assert!(matches!(result, Err(RenameError::SourceNotFound(_))));
     }
-    std::fs::rename(source, target)?;
-    Ok(())
 }
@@ -0,0 +1,152 @@
+use colored::Colorize;
+use romv::{EntryStatus, Failure, Fix, RenamePlan, SkipReason};
+use std::path::{Path, PathBuf};
+
+/// Print the paths that were skipped and, with `verbose`, the unchanged
+/// and filtered ones and the fixes made to the new names.
+pub fn skips(plan: &RenamePlan, verbose: bool) {
+    for skip in &plan.skipped {
+        let source = skip.source.display();
+        match skip.reason {
+            SkipReason::NotFound => eprintln!("{} {source} (not found)", "skip:".yellow()),
+            SkipReason::InvalidName => eprintln!(
+                "{} cannot decode filename: {source} (see --from-encoding)",
+                "skip:".yellow()
+            ),
+            SkipReason::EmptyName => eprintln!(
+                "{} conversion produced empty name: {source}",
+                "skip:".yellow()
+            ),
+            SkipReason::TooLong { length, limit } => eprintln!(
+                "{} {source} (name too long: {length} bytes, limit {limit})",
+                "skip:".yellow()
+            ),
+            SkipReason::Conflict => eprintln!(
+                "{} {source} (conflicts with {})",
+                "skip:".yellow(),
+                skip.target.as_deref().unwrap_or(Path::new("")).display()
+            ),
+            SkipReason::Unchanged if verbose => {
+                eprintln!("{} {source} (unchanged)", "skip:".dimmed())
+            }
+            SkipReason::Unchanged => {}
+        }
+    }
+    if !verbose {
+        return;
+    }
+    for source in &plan.filtered {
+        eprintln!("{} {} (filtered)", "skip:".dimmed(), source.display());
+    }
+    for entry in &plan.entries {
+        let source = entry.source.display();
+        for fix in &entry.fixes {
+            match fix {
+                Fix::Truncated { .. } => eprintln!(
+                    "{} {source} ({fix}): {}",
+                    "truncate:".cyan(),
+                    entry
+                        .target
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                ),
+                _ => eprintln!("{} {source} ({fix})", "sanitize:".cyan()),
+            }
+        }
+    }
+}
+
+pub fn collisions(collisions: &[(PathBuf, Vec<PathBuf>)]) {
+    for (target, sources) in collisions {
+        eprintln!(
+            "{} multiple files would rename to {}:",
+            "collision:".red().bold(),
+            target.display()
+        );
+        for source in sources {
+            eprintln!("  - {}", source.display());
+        }
+    }
+}
+
+pub fn non_ascii(found: &[(PathBuf, Vec<char>)]) {
+    for (target, chars) in found {
+        let chars: Vec<String> = chars
+            .iter()
+            .map(|&c| format!("{c:?} (U+{:04X})", c as u32))
+            .collect();
+        eprintln!(
+            "{} non-ASCII in {}: {}",
+            "error:".red().bold(),
+            target.display(),
+            chars.join(", ")
+        );
+    }
+}
+
+pub fn failures(failures: &[Failure]) {
+    for failure in failures {
+        let left_at = match &failure.left_at {
+            Some(temp) => format!(" (file left at {})", temp.display()),
+            None => String::new(),
+        };
+        eprintln!(
+            "{} {} -> {}: {}{left_at}",
+            "error:".red().bold(),
+            failure.source.display(),
+            failure.target.display(),
+            failure.error
+        );
+    }
+}
+
+/// Print the planned renames: ready ones on stdout, the others in red on
+/// stderr.
+pub fn plan(plan: &RenamePlan, execute: bool) {
+    for entry in &plan.entries {
+        let target_name = entry
+            .target
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        match entry.status {
+            EntryStatus::Collision => {
+                eprintln!(
+                    "{} {} {} {}",
+                    entry.source.display(),
+                    "->".bold(),
+                    target_name.red(),
+                    "(collision)".red(),
+                );
+            }
+            EntryStatus::TargetExists => {
+                eprintln!(
+                    "{} {} {} {}",
+                    entry.source.display(),
+                    "->".bold(),
+                    target_name.red(),
+                    "(already exists)".red(),
+                );
+            }
+            EntryStatus::Ready => {
+                let backup = match &entry.backup {
+                    Some(backup) => format!(
+                        " (backup: {})",
+                        backup.file_name().unwrap_or_default().to_string_lossy()
+                    ),
+                    None => String::new(),
+                };
+                println!(
+                    "{} {} {}{} {}",
+                    entry.source.display(),
+                    "->".bold(),
+                    target_name.green(),
+                    backup.yellow(),
+                    if execute { "" } else { "(dry-run)" }
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use clap::ValueEnum;
+
+/// Romanization system used to spell kana readings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum System {
+    /// Hepburn (shi, tsu, chi, fu, ji)
+    #[default]
+    Hepburn,
+    /// Kunrei-shiki (si, tu, ti, hu, zi)
+    Kunrei,
+    /// Nihon-shiki (like Kunrei-shiki, but keeps di/du/wo)
+    Nihon,
+    /// Passport Hepburn (m before b/m/p, no long vowels)
+    Passport,
+}
+
+impl System {
+    /// Long-vowel spelling used when none is chosen explicitly.
+    pub fn default_long_vowels(self) -> LongVowels {
+        match self {
+            System::Passport => LongVowels::O,
+            System::Hepburn | System::Kunrei | System::Nihon => LongVowels::Ou,
+        }
+    }
+}
+
+/// How long vowels (`とう`, `おお`, `ゆう`, `ー`) are spelled. Doubled
+/// `ああ`, `いい` and `ええ` are kept as written, as they often span two
+/// kanji (`場合` is `ba-ai`), and so is a verb's `う` (`思う` → `omou`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LongVowels {
+    /// Spell the kana as written (toukyou, koohii)
+    Ou,
+    /// Write a long o as `oh`, drop other long vowels (tohkyoh)
+    Oh,
+    /// Drop the lengthening (tokyo)
+    O,
+    /// Macron over the vowel (tōkyō)
+    #[value(alias = "ō")]
+    Macron,
+    /// Circumflex over the vowel (tôkyô)
+    #[value(alias = "ô")]
+    Circumflex,
+}
+
+/// Hepburn spelling of a single kana syllable (one kana, or a kana
+/// followed by a small kana). Mirrors the table kakasi uses so the
+/// default output stays unchanged.
+fn hepburn(kana: &str) -> Option<&'static str> {
+    let r = match kana {
+        "あ" | "ぁ" => "a",
+        "い" | "ぃ" | "ゐ" => "i",
+        "う" | "ぅ" => "u",
+        "え" | "ぇ" | "ゑ" => "e",
+        "お" | "ぉ" => "o",
+        "か" | "ゕ" => "ka",
+        "き" => "ki",
+        "く" => "ku",
+        "け" | "ゖ" => "ke",
+        "こ" => "ko",
+        "が" => "ga",
+        "ぎ" => "gi",
+        "ぐ" => "gu",
+        "げ" => "ge",
+        "ご" => "go",
+        "さ" => "sa",
+        "し" => "shi",
+        "す" => "su",
+        "せ" => "se",
+        "そ" => "so",
+        "ざ" => "za",
+        "じ" | "ぢ" => "ji",
+        "ず" | "づ" => "zu",
+        "ぜ" => "ze",
+        "ぞ" => "zo",
+        "た" => "ta",
+        "ち" => "chi",
+        "つ" => "tsu",
+        "て" => "te",
+        "と" => "to",
+        "だ" => "da",
+        "で" => "de",
+        "ど" => "do",
+        "な" => "na",
+        "に" => "ni",
+        "ぬ" => "nu",
+        "ね" => "ne",
+        "の" => "no",
+        "は" => "ha",
+        "ひ" => "hi",
+        "ふ" => "fu",
+        "へ" => "he",
+        "ほ" => "ho",
+        "ば" => "ba",
+        "び" => "bi",
+        "ぶ" => "bu",
+        "べ" => "be",
+        "ぼ" => "bo",
+        "ぱ" => "pa",
+        "ぴ" => "pi",
+        "ぷ" => "pu",
+        "ぺ" => "pe",
+        "ぽ" => "po",
+        "ま" => "ma",
+        "み" => "mi",
+        "む" => "mu",
+        "め" => "me",
+        "も" => "mo",
+        "や" | "ゃ" => "ya",
+        "ゆ" | "ゅ" => "yu",
+        "よ" | "ょ" => "yo",
+        "ら" => "ra",
+        "り" => "ri",
+        "る" => "ru",
+        "れ" => "re",
+        "ろ" => "ro",
+        "わ" | "ゎ" => "wa",
+        "を" => "wo",
+        "ゔ" => "vu",
+        "きゃ" => "kya",
+        "きゅ" => "kyu",
+        "きょ" => "kyo",
+        "ぎゃ" => "gya",
+        "ぎゅ" => "gyu",
+        "ぎょ" => "gyo",
+        "しゃ" => "sha",
+        "しゅ" => "shu",
+        "しょ" => "sho",
+        "じゃ" | "ぢゃ" => "ja",
+        "じゅ" | "ぢゅ" => "ju",
+        "じょ" | "ぢょ" => "jo",
+        "ちゃ" => "cha",
+        "ちゅ" => "chu",
+        "ちょ" => "cho",
+        "ちぇ" => "che",
+        "でぃ" => "di",
+        "にゃ" => "nya",
+        "にゅ" => "nyu",
+        "にょ" => "nyo",
+        "ひゃ" => "hya",
+        "ひゅ" => "hyu",
+        "ひょ" => "hyo",
+        "びゃ" => "bya",
+        "びゅ" => "byu",
+        "びょ" => "byo",
+        "ぴゃ" => "pya",
+        "ぴゅ" => "pyu",
+        "ぴょ" => "pyo",
+        "ふぁ" => "fa",
+        "ふぃ" => "fi",
+        "ふぇ" => "fe",
+        "ふぉ" => "fo",
+        "みゃ" => "mya",
+        "みゅ" => "myu",
+        "みょ" => "myo",
+        "りゃ" => "rya",
+        "りゅ" => "ryu",
+        "りょ" => "ryo",
+        "ゔぁ" => "va",
+        "ゔぃ" => "vi",
+        "ゔぇ" => "ve",
+        "ゔぉ" => "vo",
+        // Small katakana for Ainu, which write a syllable-final consonant
+        "ㇰ" => "k",
+        "ㇱ" | "ㇲ" => "s",
+        "ㇳ" => "t",
+        "ㇴ" => "n",
+        "ㇵ" | "ㇶ" | "ㇷ" | "ㇸ" | "ㇹ" => "h",
+        "ㇷ\u{309A}" => "p",
+        "ㇺ" => "m",
+        "ㇻ" | "ㇼ" | "ㇽ" | "ㇾ" | "ㇿ" => "r",
+        _ => return None,
+    };
+    Some(r)
+}
+
+/// Kunrei-shiki spellings that differ from Hepburn.
+fn kunrei(kana: &str) -> Option<&'static str> {
+    let r = match kana {
+        "し" => "si",
+        "しゃ" => "sya",
+        "しゅ" => "syu",
+        "しょ" => "syo",
+        "じ" | "ぢ" => "zi",
+        "じゃ" | "ぢゃ" => "zya",
+        "じゅ" | "ぢゅ" => "zyu",
+        "じょ" | "ぢょ" => "zyo",
+        "ち" => "ti",
+        "ちゃ" => "tya",
+        "ちゅ" => "tyu",
+        "ちょ" => "tyo",
+        "ちぇ" => "tye",
+        "つ" => "tu",
+        "ふ" => "hu",
+        "を" => "o",
+        _ => return None,
+    };
+    Some(r)
+}
+
+/// Nihon-shiki spellings that differ from Kunrei-shiki.
+fn nihon(kana: &str) -> Option<&'static str> {
+    let r = match kana {
+        "ぢ" => "di",
+        "ぢゃ" => "dya",
+        "ぢゅ" => "dyu",
+        "ぢょ" => "dyo",
+        "づ" => "du",
+        "を" => "wo",
+        "ゐ" => "wi",
+        "ゑ" => "we",
+        _ => return None,
+    };
+    Some(r)
+}
+
+fn syllable(kana: &str, system: System) -> Option<&'static str> {
+    match system {
+        System::Hepburn => hepburn(kana),
+        System::Passport => match kana {
+            "を" => Some("o"),
+            _ => hepburn(kana),
+        },
+        System::Kunrei => kunrei(kana).or_else(|| hepburn(kana)),
+        System::Nihon => nihon(kana)
+            .or_else(|| kunrei(kana))
+            .or_else(|| hepburn(kana)),
+    }
+}
+
+/// A unit of a kana reading. Sokuon (`っ`), hatsuon (`ん`) and the
+/// prolonged sound mark (`ー`) are spelled depending on their neighbours.
+#[derive(Debug)]
+enum Unit<'a> {
+    Syllable { kana: &'a str, romaji: &'static str },
+    Sokuon,
+    Hatsuon,
+    Choon,
+    Other(char),
+}
+
+fn parse(kana: &str, system: System) -> Vec<Unit<'_>> {
+    let mut units = Vec::new();
+    let mut rest = kana;
+
+    while let Some(c) = rest.chars().next() {
+        let len = c.len_utf8();
+        let unit = match c {
+            'っ' => Unit::Sokuon,
+            'ん' => Unit::Hatsuon,
+            'ー' => Unit::Choon,
+            _ => {
+                // Prefer a two-kana syllable (e.g. `きゃ`) over a single kana
+                let pair_len = rest[len..].chars().next().map_or(0, |n| len + n.len_utf8());
+                let pair = (pair_len > 0)
+                    .then(|| &rest[..pair_len])
+                    .and_then(|p| syllable(p, system).map(|r| (p, r)));
+                match pair.or_else(|| syllable(&rest[..len], system).map(|r| (&rest[..len], r))) {
+                    Some((kana, romaji)) => Unit::Syllable { kana, romaji },
+                    None => Unit::Other(c),
+                }
+            }
+        };
+        let consumed = match unit {
+            Unit::Syllable { kana, .. } => kana.len(),
+            _ => len,
+        };
+        units.push(unit);
+        rest = &rest[consumed..];
+    }
+
+    units
+}
+
+/// Whether `kana` only lengthens the vowel of the preceding syllable
+/// (`おう`, `おお`, `うう`), as opposed to starting a new one.
+fn lengthens(prev: Option<&Unit>, kana: &str) -> bool {
+    let Some(Unit::Syllable { romaji, .. }) = prev else {
+        return false;
+    };
+    match kana {
+        "う" => romaji.ends_with('o') || romaji.ends_with('u'),
+        "お" => romaji.ends_with('o'),
+        _ => false,
+    }
+}
+
+/// Lengthen the vowel at the end of `out` according to `mode`.
+/// Does nothing if `out` does not end with a vowel.
+fn lengthen(out: &mut String, mode: LongVowels) {
+    let Some(v) = out.chars().last().filter(|c| "aiueo".contains(*c)) else {
+        return;
+    };
+    let accented = match (mode, v) {
+        (LongVowels::Ou | LongVowels::O, _) => return,
+        (LongVowels::Oh, 'o') => {
+            out.push('h');
+            return;
+        }
+        (LongVowels::Oh, _) => return,
+        (LongVowels::Macron, 'a') => 'ā',
+        (LongVowels::Macron, 'i') => 'ī',
+        (LongVowels::Macron, 'u') => 'ū',
+        (LongVowels::Macron, 'e') => 'ē',
+        (LongVowels::Macron, _) => 'ō',
+        (LongVowels::Circumflex, 'a') => 'â',
+        (LongVowels::Circumflex, 'i') => 'î',
+        (LongVowels::Circumflex, 'u') => 'û',
+        (LongVowels::Circumflex, 'e') => 'ê',
+        (LongVowels::Circumflex, _) => 'ô',
+    };
+    out.pop();
+    out.push(accented);
+}
+
+/// Spell a hiragana reading (as produced by kakasi) in `system`, with
+/// long vowels written according to `long_vowels`.
+///
+/// Characters that are not kana are passed through unchanged, except
+/// for the katakana middle dot `・`, which is dropped.
+pub fn romanize(kana: &str, system: System, long_vowels: LongVowels) -> String {
+    romanize_with_okurigana(kana, &[], system, long_vowels)
+}
+
+/// Like [`romanize`], but the kana at the character offsets in
+/// `okurigana` always start a syllable of their own, so that a verb's
+/// `う` is not read as a long vowel (`おもう` → `omou`, not `omō`).
+pub fn romanize_with_okurigana(
+    kana: &str,
+    okurigana: &[usize],
+    system: System,
+    long_vowels: LongVowels,
+) -> String {
+    let units = parse(kana, system);
+    let mut out = String::with_capacity(kana.len());
+    let small_tsu = match system {
+        System::Kunrei | System::Nihon => "tu",
+        System::Hepburn | System::Passport => "tsu",
+    };
+
+    let mut offset = 0;
+    for (i, unit) in units.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| &units[p]);
+        let start = offset;
+        offset += match unit {
+            Unit::Syllable { kana, .. } => kana.chars().count(),
+            _ => 1,
+        };
+        let next_romaji = match units.get(i + 1) {
+            Some(Unit::Syllable { romaji, .. }) => Some(*romaji),
+            _ => None,
+        };
+
+        match unit {
+            Unit::Syllable { kana, romaji } => {
+                if long_vowels != LongVowels::Ou
+                    && !okurigana.contains(&start)
+                    && lengthens(prev, kana)
+                {
+                    lengthen(&mut out, long_vowels);
+                    continue;
+                }
+                out.push_str(romaji);
+            }
+            Unit::Sokuon => match next_romaji {
+                // Hepburn spells `っち` as `tchi`; the kunrei systems have no `ch`
+                Some(r) if r.starts_with("ch") => out.push('t'),
+                Some(r) if r.starts_with(|c: char| "kgsztdhbpfjyrv".contains(c)) => {
+                    out.push(r.chars().next().unwrap_or_default());
+                }
+                _ => out.push_str(small_tsu),
+            },
+            Unit::Hatsuon => match next_romaji {
+                Some(r) if system == System::Passport && r.starts_with(['b', 'm', 'p']) => {
+                    out.push('m');
+                }
+                Some(r)
+                    if system != System::Passport && r.starts_with(['a', 'i', 'u', 'e', 'o']) =>
+                {
+                    out.push_str("n'");
+                }
+                _ => out.push('n'),
+            },
+            Unit::Choon => match long_vowels {
+                LongVowels::Ou => out.push(out.chars().last().unwrap_or('-')),
+                _ => lengthen(&mut out, long_vowels),
+            },
+            Unit::Other('・') => {}
+            Unit::Other(c) => out.push(*c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hepburn_spellings() {
+        let r = |k| romanize(k, System::Hepburn, LongVowels::Ou);
+        assert_eq!(r("しんぶん"), "shinbun");
+        assert_eq!(r("ちず"), "chizu");
+        assert_eq!(r("つづく"), "tsuzuku");
+        assert_eq!(r("ふじ"), "fuji");
+        assert_eq!(r("じゃま"), "jama");
+        assert_eq!(r("まっちゃ"), "matcha");
+        assert_eq!(r("こんいん"), "kon'in");
+        assert_eq!(r("とうきょう"), "toukyou");
+        assert_eq!(r("こーひー"), "koohii");
+    }
+
+    #[test]
+    fn non_kana_passed_through() {
+        assert_eq!(romanize("ab・猫", System::Hepburn, LongVowels::Ou), "ab猫");
+    }
+
+    #[test]
+    fn okurigana_u_is_not_a_long_vowel() {
+        let r = |k, o: &[usize], m| romanize_with_okurigana(k, o, System::Hepburn, m);
+        assert_eq!(r("おう", &[1], LongVowels::O), "ou");
+        assert_eq!(r("おもう", &[2], LongVowels::Macron), "omou");
+        assert_eq!(r("おもうように", &[2], LongVowels::Macron), "omouyōni");
+        assert_eq!(r("おう", &[], LongVowels::Macron), "ō");
+    }
+}
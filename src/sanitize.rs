@@ -0,0 +1,271 @@
+use clap::ValueEnum;
+use std::fmt;
+
+/// Set of characters a sanitized name is restricted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Sanitize {
+    /// POSIX portable filename characters: `A-Z a-z 0-9 . _ -`
+    Posix,
+    /// Names valid on Windows: no `<>:"/\|?*`, control characters,
+    /// trailing dots or spaces, or reserved names like `CON`
+    Windows,
+    /// URL-safe characters: `A-Z a-z 0-9 . _ - ~`
+    Url,
+}
+
+/// Device names Windows reserves regardless of extension.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+impl Sanitize {
+    fn allows(self, c: char) -> bool {
+        match self {
+            Sanitize::Posix => c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'),
+            Sanitize::Windows => {
+                !c.is_control()
+                    && !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+            }
+            Sanitize::Url => c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '~'),
+        }
+    }
+}
+
+/// A change made to a converted name by sanitizing or truncation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fix {
+    /// Unsafe characters replaced with the separator
+    Replaced(Vec<char>),
+    /// Runs of separators collapsed into one
+    Collapsed,
+    /// Separators trimmed at the start or end of the name or its parts,
+    /// and parts left empty dropped
+    Trimmed,
+    /// Trailing dots and spaces removed (Windows)
+    TrailingDots,
+    /// Reserved device name suffixed with the separator (Windows)
+    Reserved(String),
+    /// Name cut at a word boundary to fit the length limit
+    Truncated {
+        /// Length of the converted name in bytes before truncation
+        length: usize,
+        limit: usize,
+    },
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fix::Replaced(chars) => {
+                let chars: Vec<String> = chars.iter().map(|c| format!("{c:?}")).collect();
+                write!(f, "replaced unsafe {}", chars.join(", "))
+            }
+            Fix::Collapsed => write!(f, "collapsed repeated separators"),
+            Fix::Trimmed => write!(f, "trimmed leading/trailing separators"),
+            Fix::TrailingDots => write!(f, "removed trailing dots/spaces"),
+            Fix::Reserved(name) => write!(f, "suffixed reserved name {name:?}"),
+            Fix::Truncated { length, limit } => write!(f, "{length} bytes, limit {limit}"),
+        }
+    }
+}
+
+/// Restrict `name` (without leading dots) to the characters `mode`
+/// allows. Unsafe characters become `separator` (or `_` if the separator
+/// is unsafe itself), runs of separators (`separators`, `_`, `-` and
+/// space) are collapsed and separators next to the ends of the name and
+/// to dots are trimmed. Each rule that
+/// changed the name is recorded in `fixes`.
+pub fn sanitize(
+    name: &str,
+    mode: Sanitize,
+    separator: char,
+    separators: &[char],
+    fixes: &mut Vec<Fix>,
+) -> String {
+    let replacement = if mode.allows(separator) {
+        separator
+    } else {
+        '_'
+    };
+    let is_separator =
+        |c: char| c == replacement || separators.contains(&c) || matches!(c, '_' | '-' | ' ');
+
+    let mut replaced = Vec::new();
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if mode.allows(c) {
+                c
+            } else {
+                if !replaced.contains(&c) {
+                    replaced.push(c);
+                }
+                replacement
+            }
+        })
+        .collect();
+    if !replaced.is_empty() {
+        fixes.push(Fix::Replaced(replaced));
+    }
+
+    // A run of mixed separators (`_-_`) becomes the replacement
+    let mut collapsed = String::with_capacity(name.len());
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !is_separator(c) {
+            collapsed.push(c);
+            continue;
+        }
+        let mut run = vec![c];
+        while let Some(&next) = chars.peek()
+            && is_separator(next)
+        {
+            run.push(next);
+            chars.next();
+        }
+        collapsed.push(if run.len() > 1 && run.contains(&replacement) {
+            replacement
+        } else {
+            c
+        });
+    }
+    if collapsed != name {
+        fixes.push(Fix::Collapsed);
+    }
+
+    let mut buf = [0; 4];
+    let stem_only: &str = replacement.encode_utf8(&mut buf);
+    let mut parts = Vec::new();
+    for (i, part) in collapsed.split('.').enumerate() {
+        match part.trim_matches(is_separator) {
+            // Keep a stem made only of separators (`???.txt` → `_.txt`)
+            "" if i == 0 && !part.is_empty() => parts.push(stem_only),
+            // Drop another part left empty together with its dot
+            "" if !part.is_empty() => {}
+            trimmed => parts.push(trimmed),
+        }
+    }
+    let trimmed = parts.join(".");
+    if trimmed != collapsed {
+        fixes.push(Fix::Trimmed);
+    }
+
+    let mut name = trimmed;
+    if mode == Sanitize::Windows {
+        let kept = name.trim_end_matches(['.', ' ']);
+        if kept.len() != name.len() && !kept.is_empty() {
+            name = kept.to_string();
+            fixes.push(Fix::TrailingDots);
+        }
+        let base = name.split('.').next().unwrap_or_default();
+        if RESERVED
+            .iter()
+            .any(|r| r.eq_ignore_ascii_case(base.trim_end()))
+        {
+            fixes.push(Fix::Reserved(base.to_string()));
+            name.insert(base.len(), replacement);
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, mode: Sanitize) -> (String, Vec<Fix>) {
+        let mut fixes = Vec::new();
+        let name = sanitize(name, mode, '_', &[], &mut fixes);
+        (name, fixes)
+    }
+
+    #[test]
+    fn windows_replaces_reserved_characters() {
+        let (name, fixes) = run("kaigi: ashita?.txt", Sanitize::Windows);
+        assert_eq!(name, "kaigi_ashita.txt");
+        assert_eq!(
+            fixes,
+            vec![Fix::Replaced(vec![':', '?']), Fix::Collapsed, Fix::Trimmed]
+        );
+    }
+
+    #[test]
+    fn windows_trailing_dots_and_reserved_names() {
+        assert_eq!(run("memo. .", Sanitize::Windows).0, "memo");
+        assert_eq!(run("con.txt", Sanitize::Windows).0, "con_.txt");
+        assert_eq!(run("LPT1", Sanitize::Windows).0, "LPT1_");
+        assert_eq!(run("console.txt", Sanitize::Windows).0, "console.txt");
+    }
+
+    #[test]
+    fn posix_and_url_character_sets() {
+        assert_eq!(run("a~b,c.txt", Sanitize::Posix).0, "a_b_c.txt");
+        assert_eq!(run("a~b,c.txt", Sanitize::Url).0, "a~b_c.txt");
+        assert_eq!(run("tōkyō.txt", Sanitize::Posix).0, "t_ky.txt");
+    }
+
+    #[test]
+    fn collapses_and_trims_separators() {
+        let (name, fixes) = run("__a___b_._txt_", Sanitize::Posix);
+        assert_eq!(name, "a_b.txt");
+        assert_eq!(fixes, vec![Fix::Collapsed, Fix::Trimmed]);
+        assert_eq!(run("???", Sanitize::Posix).0, "_");
+        assert_eq!(run("???.txt", Sanitize::Posix).0, "_.txt");
+    }
+
+    #[test]
+    fn unsafe_separator_falls_back_to_underscore() {
+        let mut fixes = Vec::new();
+        assert_eq!(
+            sanitize("a b:c", Sanitize::Posix, ' ', &[], &mut fixes),
+            "a_b_c"
+        );
+        assert_eq!(
+            sanitize("a-b--c", Sanitize::Posix, '-', &[], &mut fixes),
+            "a-b-c"
+        );
+    }
+
+    #[test]
+    fn collapses_mixed_separators() {
+        assert_eq!(
+            run("kaigi_-_shiryou.txt", Sanitize::Posix).0,
+            "kaigi_shiryou.txt"
+        );
+        assert_eq!(
+            run("kaigi - shiryou.txt", Sanitize::Windows).0,
+            "kaigi shiryou.txt"
+        );
+        assert_eq!(run("a--b.txt", Sanitize::Posix).0, "a-b.txt");
+    }
+
+    #[test]
+    fn drops_emptied_parts() {
+        let (name, fixes) = run("shiryou.??????", Sanitize::Posix);
+        assert_eq!(name, "shiryou");
+        assert_eq!(
+            fixes,
+            vec![Fix::Replaced(vec!['?']), Fix::Collapsed, Fix::Trimmed]
+        );
+        assert_eq!(run("a.??.txt", Sanitize::Posix).0, "a.txt");
+        assert_eq!(run("a..txt", Sanitize::Posix).0, "a..txt");
+    }
+
+    #[test]
+    fn multibyte_separators() {
+        let mut fixes = Vec::new();
+        assert_eq!(
+            sanitize("♡♡.txt", Sanitize::Windows, '_', &['♡'], &mut fixes),
+            "_.txt"
+        );
+        assert_eq!(
+            sanitize("__.txt", Sanitize::Windows, '♡', &['_'], &mut fixes),
+            "♡.txt"
+        );
+        assert_eq!(
+            sanitize("a♡?♡b", Sanitize::Windows, '♡', &[], &mut fixes),
+            "a♡b"
+        );
+    }
+}
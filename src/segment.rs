@@ -0,0 +1,194 @@
+use crate::romaji::{self, LongVowels, System};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Kanji,
+    Hiragana,
+    Katakana,
+}
+
+fn script(c: char) -> Script {
+    match c {
+        // Small ka/ke are counters read with the kanji around them (一ヶ月)
+        'ヵ' | 'ヶ' => Script::Kanji,
+        '\u{3040}'..='\u{309F}' => Script::Hiragana,
+        '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+            Script::Katakana
+        }
+        _ => Script::Kanji,
+    }
+}
+
+/// A chunk of a Japanese run written in one script. Hiragana directly
+/// following kanji (okurigana and particles) belongs to the kanji chunk;
+/// `trailing_kana` counts those characters.
+struct Chunk {
+    text: String,
+    trailing_kana: usize,
+}
+
+fn chunks(run: &str) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut lead = None;
+
+    for c in run.chars() {
+        // The middle dot separates words and is not part of either
+        if c == '・' {
+            lead = None;
+            continue;
+        }
+        let s = script(c);
+        let continues = match (lead, s) {
+            (None, _) => false,
+            // The prolonged sound mark extends whatever precedes it
+            (Some(_), _) if c == 'ー' => true,
+            (Some(Script::Kanji), Script::Hiragana) => true,
+            (Some(Script::Kanji), Script::Kanji) => {
+                chunks.last().is_some_and(|chunk| chunk.trailing_kana == 0)
+            }
+            (Some(l), s) => l == s,
+        };
+
+        if continues && let Some(chunk) = chunks.last_mut() {
+            chunk.text.push(c);
+            if lead == Some(Script::Kanji) && s == Script::Hiragana {
+                chunk.trailing_kana += 1;
+            }
+        } else {
+            chunks.push(Chunk {
+                text: c.to_string(),
+                trailing_kana: 0,
+            });
+            lead = Some(s);
+        }
+    }
+
+    chunks
+}
+
+/// Character offsets into `converted.hiragana` at which kakasi started a
+/// new word in its romaji output.
+///
+/// kakasi only marks word boundaries in the romaji, so each boundary is
+/// located by spelling growing prefixes of the reading the way kakasi does
+/// until they match the romaji words seen so far.
+fn kakasi_boundaries(converted: &kakasi::KakasiResult) -> Vec<usize> {
+    let reading: Vec<char> = converted.hiragana.chars().collect();
+    let romaji = converted.romaji.to_lowercase();
+    let words: Vec<&str> = romaji.split(' ').filter(|w| !w.is_empty()).collect();
+
+    let mut boundaries = Vec::new();
+    let mut spelled = String::new();
+    let mut start = 0;
+    for word in words.iter().take(words.len().saturating_sub(1)) {
+        spelled.push_str(word);
+        let end = (start + 1..reading.len()).find(|&end| {
+            let prefix: String = reading[..end].iter().collect();
+            romaji::romanize(&prefix, System::Hepburn, LongVowels::Ou) == spelled
+        });
+        if let Some(end) = end {
+            boundaries.push(end);
+            start = end;
+        }
+    }
+
+    boundaries
+}
+
+/// Kana reading of Japanese text.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reading {
+    pub kana: String,
+    /// Offsets in characters into `kana` at which okurigana start
+    pub okurigana: Vec<usize>,
+}
+
+/// Split a run of Japanese text into words and return the kana reading
+/// of each word.
+///
+/// Words follow the boundaries kakasi puts between dictionary entries,
+/// except that okurigana stay attached to their kanji (`新しい` is one
+/// word, not `新し` + `い`). Script changes (kanji to katakana, etc.)
+/// and the middle dot `・` always start a new word.
+pub fn words(run: &str) -> Vec<Reading> {
+    let mut words = Vec::new();
+
+    for chunk in chunks(run) {
+        let converted = kakasi::convert(&chunk.text);
+        let reading: Vec<char> = converted.hiragana.chars().collect();
+        let okurigana_start = reading.len().saturating_sub(chunk.trailing_kana);
+
+        let mut start = 0;
+        for end in kakasi_boundaries(&converted) {
+            if chunk.trailing_kana > 0 && end > okurigana_start {
+                continue;
+            }
+            words.push(Reading {
+                kana: reading[start..end].iter().collect(),
+                okurigana: Vec::new(),
+            });
+            start = end;
+        }
+        words.push(Reading {
+            kana: reading[start..].iter().collect(),
+            okurigana: (chunk.trailing_kana > 0)
+                .then(|| okurigana_start.saturating_sub(start))
+                .into_iter()
+                .collect(),
+        });
+    }
+
+    words
+}
+
+/// Reading of a whole run of Japanese text: its [`words`] joined.
+pub fn reading(run: &str) -> Reading {
+    let mut reading = Reading::default();
+    for word in words(run) {
+        let offset = reading.kana.chars().count();
+        reading
+            .okurigana
+            .extend(word.okurigana.iter().map(|o| offset + o));
+        reading.kana.push_str(&word.kana);
+    }
+    reading
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kana(run: &str) -> Vec<String> {
+        words(run).into_iter().map(|w| w.kana).collect()
+    }
+
+    #[test]
+    fn okurigana_stay_with_kanji() {
+        assert_eq!(kana("新しいファイル"), ["あたらしい", "ふぁいる"]);
+    }
+
+    #[test]
+    fn kakasi_boundaries_within_kanji() {
+        assert_eq!(kana("会議資料"), ["かいぎ", "しりょう"]);
+        assert_eq!(kana("日本語の文書"), ["にほんご", "の", "ぶんしょ"]);
+    }
+
+    #[test]
+    fn middle_dot_splits_words() {
+        assert_eq!(kana("テスト・ファイル"), ["てすと", "ふぁいる"]);
+    }
+
+    #[test]
+    fn prolonged_sound_mark_continues_word() {
+        assert_eq!(kana("コーヒー"), ["こーひー"]);
+    }
+
+    #[test]
+    fn okurigana_offsets() {
+        assert_eq!(words("追う")[0].okurigana, [1]);
+        assert_eq!(words("ファイル")[0].okurigana, [] as [usize; 0]);
+        let reading = reading("思う東京");
+        assert_eq!(reading.kana, "おもうとうきょう");
+        assert_eq!(reading.okurigana, [2]);
+    }
+}
@@ -0,0 +1,335 @@
+use clap::ValueEnum;
+use unicode_normalization::UnicodeNormalization;
+
+/// Non-Japanese script that can be transliterated to ASCII.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Script {
+    /// Korean Hangul, Revised Romanization (`한국어` → `hangugeo`)
+    Hangul,
+    /// Cyrillic (`Москва` → `Moskva`)
+    Cyrillic,
+    /// Greek (`Αθήνα` → `Athina`)
+    Greek,
+    /// Latin letters with diacritics (`café` → `cafe`)
+    Latin,
+}
+
+impl Script {
+    fn contains(self, c: char) -> bool {
+        match self {
+            Script::Hangul => ('\u{AC00}'..='\u{D7A3}').contains(&c),
+            Script::Cyrillic => ('\u{0400}'..='\u{04FF}').contains(&c),
+            Script::Greek => matches!(c, '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}'),
+            Script::Latin => matches!(c, '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}'),
+        }
+    }
+
+    fn transliterate(self, run: &str) -> String {
+        match self {
+            Script::Hangul => hangul(run),
+            Script::Cyrillic => by_letter(run, cyrillic),
+            Script::Greek => greek(run),
+            Script::Latin => run.chars().map(latin).collect(),
+        }
+    }
+}
+
+/// Transliterate the characters of `s` that belong to one of `scripts`,
+/// leaving everything else (including Japanese) as it is.
+pub fn transliterate(s: &str, scripts: &[Script]) -> String {
+    if scripts.is_empty() {
+        return s.to_string();
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut run = String::new();
+    let mut current = None;
+    for c in s.chars() {
+        let script = scripts.iter().copied().find(|script| script.contains(c));
+        if script != current {
+            if let Some(script) = current {
+                out.push_str(&script.transliterate(&run));
+            }
+            run.clear();
+            current = script;
+        }
+        match script {
+            Some(_) => run.push(c),
+            None => out.push(c),
+        }
+    }
+    if let Some(script) = current {
+        out.push_str(&script.transliterate(&run));
+    }
+    out
+}
+
+/// Apply a lowercase letter table, keeping the case of each letter
+/// (`Ж` → `Zh`).
+fn by_letter(run: &str, table: fn(char) -> Option<&'static str>) -> String {
+    let mut out = String::with_capacity(run.len());
+    for c in run.chars() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        match table(lower) {
+            Some(latin) if c != lower => {
+                let mut letters = latin.chars();
+                if let Some(first) = letters.next() {
+                    out.extend(first.to_uppercase());
+                    out.extend(letters);
+                }
+            }
+            Some(latin) => out.push_str(latin),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+const INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+const VOWELS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+/// Final consonants at the end of a word or before a consonant.
+const FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+/// Final consonants carried over to a following syllable that starts
+/// with a vowel (`국어` → `gugeo`).
+const LINKED_FINALS: [&str; 28] = [
+    "", "g", "kk", "gs", "n", "nj", "n", "d", "r", "lg", "lm", "lb", "ls", "lt", "lp", "r", "m",
+    "b", "bs", "s", "ss", "ng", "j", "ch", "k", "t", "p", "",
+];
+
+/// Revised Romanization of Korean, with linking of final consonants
+/// into a following vowel and `ㄹㄹ`, `ㄹㄴ` as `ll`.
+fn hangul(run: &str) -> String {
+    // (initial, vowel, final) indices of each syllable
+    let syllables: Vec<(usize, usize, usize)> = run
+        .chars()
+        .map(|c| {
+            let code = c as usize - 0xAC00;
+            (code / 588, code % 588 / 28, code % 28)
+        })
+        .collect();
+
+    let mut out = String::new();
+    for (i, &(initial, vowel, _)) in syllables.iter().enumerate() {
+        let after_l = i > 0 && syllables[i - 1].2 == 8;
+        out.push_str(match initial {
+            2 | 5 if after_l => "l",
+            _ => INITIALS[initial],
+        });
+        out.push_str(VOWELS[vowel]);
+        let last = syllables[i].2;
+        out.push_str(match syllables.get(i + 1) {
+            Some(&(11, _, _)) => LINKED_FINALS[last],
+            _ => FINALS[last],
+        });
+    }
+    out
+}
+
+fn cyrillic(c: char) -> Option<&'static str> {
+    let r = match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'ґ' => "g",
+        'д' => "d",
+        'е' => "e",
+        'ё' => "yo",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' => "i",
+        'і' => "i",
+        'ї' => "yi",
+        'й' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ў' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ы' => "y",
+        'э' => "e",
+        'ю' => "yu",
+        'я' => "ya",
+        _ => return None,
+    };
+    Some(r)
+}
+
+fn greek_letter(c: char) -> Option<&'static str> {
+    let r = match c {
+        'α' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' => "e",
+        'ζ' => "z",
+        'η' => "i",
+        'θ' => "th",
+        'ι' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' => "o",
+        _ => return None,
+    };
+    Some(r)
+}
+
+/// Greek after ELOT 743: accents are dropped, `ου` is `ou` and `αυ`,
+/// `ευ`, `ηυ` are `av`, `ev`, `iv`.
+fn greek(run: &str) -> String {
+    let bare: String = run
+        .nfd()
+        .filter(|c| !('\u{0300}'..='\u{036F}').contains(c))
+        .collect();
+    let mut out = String::with_capacity(bare.len());
+    let mut prev = None;
+    for c in bare.chars() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        let latin = match (prev, lower) {
+            (Some('ο'), 'υ') => "u",
+            (Some('α' | 'ε' | 'η'), 'υ') => "v",
+            _ => "",
+        };
+        if latin.is_empty() {
+            out.push_str(&by_letter(&c.to_string(), greek_letter));
+        } else if c != lower {
+            out.push_str(&latin.to_uppercase());
+        } else {
+            out.push_str(latin);
+        }
+        prev = Some(lower);
+    }
+    out
+}
+
+/// Strip diacritics from a Latin letter, spelling out letters that do
+/// not decompose (`ß` → `ss`, `ø` → `o`). Anything else without an ASCII
+/// base (`÷`, `ɐ`) is kept as it is.
+fn latin(c: char) -> String {
+    let spelled = match c {
+        'ß' => "ss",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'đ' | 'ð' => "d",
+        'Đ' | 'Ð' => "D",
+        'ł' => "l",
+        'Ł' => "L",
+        'þ' => "th",
+        'Þ' => "TH",
+        'ı' => "i",
+        'ħ' => "h",
+        'Ħ' => "H",
+        'ŋ' => "ng",
+        'Ŋ' => "NG",
+        'ƒ' => "f",
+        'Ƒ' => "F",
+        'ŧ' => "t",
+        'Ŧ' => "T",
+        'ŀ' => "l",
+        'Ŀ' => "L",
+        'ŉ' => "n",
+        'ĸ' => "q",
+        'ſ' => "s",
+        'ƀ' => "b",
+        'ƶ' => "z",
+        'Ƶ' => "Z",
+        _ => {
+            let base: String = c.nfd().filter(|c| c.is_ascii()).collect();
+            return if base.is_empty() { c.to_string() } else { base };
+        }
+    };
+    spelled.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all(s: &str) -> String {
+        transliterate(s, Script::value_variants())
+    }
+
+    #[test]
+    fn hangul_revised_romanization() {
+        assert_eq!(all("한국어"), "hangugeo");
+        assert_eq!(all("서울"), "seoul");
+        assert_eq!(all("한글"), "hangeul");
+        assert_eq!(all("설날"), "seollal");
+    }
+
+    #[test]
+    fn cyrillic_keeps_case() {
+        assert_eq!(all("Москва"), "Moskva");
+        assert_eq!(all("Жёлтый"), "Zhyoltyy");
+        assert_eq!(all("Київ"), "Kiyiv");
+    }
+
+    #[test]
+    fn greek_drops_accents() {
+        assert_eq!(all("Αθήνα"), "Athina");
+        assert_eq!(all("Ευρώπη"), "Evropi");
+        assert_eq!(all("ουρανός"), "ouranos");
+    }
+
+    #[test]
+    fn latin_diacritics() {
+        assert_eq!(all("café_Ærøskøbing"), "cafe_AEroskobing");
+        assert_eq!(all("Straße"), "Strasse");
+    }
+
+    #[test]
+    fn latin_without_ascii_base() {
+        assert_eq!(all("ħello"), "hello");
+        assert_eq!(all("Ŋaŋ_ƒ"), "NGang_f");
+        assert_eq!(all("a÷b"), "a÷b");
+        assert_eq!(all("ɐ"), "ɐ");
+    }
+
+    #[test]
+    fn only_selected_scripts() {
+        assert_eq!(
+            transliterate("café Москва", &[Script::Latin]),
+            "cafe Москва"
+        );
+        assert_eq!(transliterate("café", &[]), "café");
+    }
+}
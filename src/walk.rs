@@ -0,0 +1,82 @@
+use colored::Colorize;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Expand `paths` by walking into directories, up to `max_depth` levels
+/// below each input (unlimited if `None`). Each directory is listed
+/// before its contents, which are sorted by name. Symlinks are not
+/// followed.
+pub fn expand(paths: &[PathBuf], max_depth: Option<usize>) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    for path in paths {
+        walk(path, 0, max_depth, &mut out);
+    }
+    out
+}
+
+fn walk(path: &Path, depth: usize, max_depth: Option<usize>, out: &mut Vec<PathBuf>) {
+    out.push(path.to_path_buf());
+
+    let is_dir = path.symlink_metadata().is_ok_and(|m| m.is_dir());
+    if !is_dir || max_depth.is_some_and(|max| depth >= max) {
+        return;
+    }
+
+    let mut children: Vec<PathBuf> = match std::fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) => {
+            eprintln!(
+                "{} cannot read directory {}: {e}",
+                "warning:".yellow(),
+                path.display()
+            );
+            return;
+        }
+    };
+    children.sort();
+
+    for child in children {
+        walk(&child, depth + 1, max_depth, out);
+    }
+}
+
+/// Add the parent directories of `paths` for which `keep` is true.
+/// Each directory is added once, however many inputs share it, and `.`
+/// components are dropped so `./a/b` and `a/c` share `a`.
+pub fn with_parents(paths: &[PathBuf], keep: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let normalize = |path: &Path| -> PathBuf {
+        path.components()
+            .filter(|c| *c != Component::CurDir)
+            .collect()
+    };
+    let mut seen: HashSet<PathBuf> = paths.iter().map(|p| normalize(p)).collect();
+    let mut out = paths.to_vec();
+    for path in paths {
+        for parent in normalize(path).ancestors().skip(1) {
+            if parent.file_name().is_none() || !keep(parent) {
+                continue;
+            }
+            if seen.insert(parent.to_path_buf()) {
+                out.push(parent.to_path_buf());
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parents_are_shared_and_filtered() {
+        let paths = [
+            PathBuf::from("写真/2024年/旅行.jpg"),
+            PathBuf::from("./写真/2024年/海.jpg"),
+            PathBuf::from("写真/album/山.jpg"),
+            PathBuf::from("./写真/2024年"),
+        ];
+        let out = with_parents(&paths, |p| p != Path::new("写真/album"));
+        assert_eq!(out[4..], [PathBuf::from("写真")]);
+    }
+}
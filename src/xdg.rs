@@ -0,0 +1,20 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Base directory for user configuration: `$XDG_CONFIG_HOME`, falling
+/// back to `~/.config`.
+pub fn config_home() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+/// Base directory for user state such as logs: `$XDG_STATE_HOME`,
+/// falling back to `~/.local/state`.
+pub fn state_home() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+}
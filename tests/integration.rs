@@ -1,6 +1,7 @@
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 fn romv_bin() -> PathBuf {
     // cargo test puts the binary in target/debug
@@ -11,6 +12,18 @@ fn romv_bin() -> PathBuf {
     path
 }
 
+/// romv command that keeps its undo journal out of the user's state dir
+/// and ignores the user's config files.
+fn romv() -> Command {
+    let mut cmd = Command::new(romv_bin());
+    cmd.env("XDG_STATE_HOME", env!("CARGO_TARGET_TMPDIR"));
+    cmd.env(
+        "XDG_CONFIG_HOME",
+        PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("no-config"),
+    );
+    cmd
+}
+
 fn create_temp_dir() -> tempfile::TempDir {
     tempfile::tempdir().expect("failed to create temp dir")
 }
@@ -21,10 +34,7 @@ fn dry_run_does_not_rename() {
     let file = dir.path().join("テスト.txt");
     fs::write(&file, "").unwrap();
 
-    let output = Command::new(romv_bin())
-        .arg(file.to_str().unwrap())
-        .output()
-        .unwrap();
+    let output = romv().arg(file.to_str().unwrap()).output().unwrap();
 
     assert!(output.status.success());
     // File should still exist with original name
@@ -46,7 +56,7 @@ fn rename_with_yes_flag() {
     let file = dir.path().join("テスト.txt");
     fs::write(&file, "content").unwrap();
 
-    let output = Command::new(romv_bin())
+    let output = romv()
         .args(["-y", file.to_str().unwrap()])
         .output()
         .unwrap();
@@ -66,7 +76,7 @@ fn no_clobber() {
     fs::write(&source, "source").unwrap();
     fs::write(&target, "existing").unwrap();
 
-    let output = Command::new(romv_bin())
+    let output = romv()
         .args(["-y", source.to_str().unwrap()])
         .output()
         .unwrap();
@@ -87,10 +97,7 @@ fn batch_collision_detection() {
     fs::write(&file, "").unwrap();
     let path_str = file.to_str().unwrap();
 
-    let output = Command::new(romv_bin())
-        .args(["-y", path_str, path_str])
-        .output()
-        .unwrap();
+    let output = romv().args(["-y", path_str, path_str]).output().unwrap();
 
     // Should detect collision and abort
     assert!(!output.status.success());
@@ -107,7 +114,7 @@ fn skip_unchanged_ascii() {
     let file = dir.path().join("hello.txt");
     fs::write(&file, "").unwrap();
 
-    let output = Command::new(romv_bin())
+    let output = romv()
         .args(["-y", file.to_str().unwrap()])
         .output()
         .unwrap();
@@ -128,7 +135,7 @@ fn stdin_input() {
     let file = dir.path().join("テスト.txt");
     fs::write(&file, "content").unwrap();
 
-    let mut child = Command::new(romv_bin())
+    let mut child = romv()
         .args(["-y"])
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
@@ -159,7 +166,7 @@ fn hidden_file_rename() {
     let file = dir.path().join(".テスト.conf");
     fs::write(&file, "").unwrap();
 
-    let output = Command::new(romv_bin())
+    let output = romv()
         .args(["-y", file.to_str().unwrap()])
         .output()
         .unwrap();
@@ -179,7 +186,7 @@ fn verbose_output() {
     let file = dir.path().join("テスト.txt");
     fs::write(&file, "").unwrap();
 
-    let output = Command::new(romv_bin())
+    let output = romv()
         .args(["-y", "-v", file.to_str().unwrap()])
         .output()
         .unwrap();
@@ -198,7 +205,7 @@ fn custom_separator() {
     let file = dir.path().join("新しい ファイル.txt");
     fs::write(&file, "").unwrap();
 
-    let output = Command::new(romv_bin())
+    let output = romv()
         .args(["-y", "-s", "-", file.to_str().unwrap()])
         .output()
         .unwrap();
@@ -211,9 +218,307 @@ fn custom_separator() {
     );
 }
 
+#[test]
+fn word_separator() {
+    let dir = create_temp_dir();
+    let file = dir.path().join("新しいファイル.txt");
+    fs::write(&file, "").unwrap();
+
+    let output = romv()
+        .args(["-y", "-w", "-", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(!file.exists());
+    assert!(
+        dir.path().join("atarashii-fairu.txt").exists(),
+        "should put '-' between Japanese words"
+    );
+}
+
+#[test]
+fn user_dictionary() {
+    let dir = create_temp_dir();
+    let dict = dir.path().join("dict.tsv");
+    fs::write(&dict, "東海林\tshoji\n").unwrap();
+    let file = dir.path().join("東海林.txt");
+    fs::write(&file, "").unwrap();
+
+    let output = romv()
+        .args([
+            "-y",
+            "--dict",
+            dict.to_str().unwrap(),
+            file.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(
+        dir.path().join("shoji.txt").exists(),
+        "should use the reading from the dictionary"
+    );
+}
+
+#[test]
+fn recursive_renames_children_before_parents() {
+    let dir = create_temp_dir();
+    let nested = dir.path().join("写真").join("旅行");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(nested.join("海.jpg"), "content").unwrap();
+
+    let output = romv()
+        .args(["-y", "-r", dir.path().join("写真").to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let target = dir.path().join("shashin").join("ryokou").join("umi.jpg");
+    assert_eq!(fs::read_to_string(target).unwrap(), "content");
+}
+
+#[test]
+fn recursive_max_depth() {
+    let dir = create_temp_dir();
+    let nested = dir.path().join("写真").join("旅行");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(nested.join("海.jpg"), "").unwrap();
+
+    let output = romv()
+        .args([
+            "-y",
+            "-r",
+            "--max-depth",
+            "1",
+            dir.path().join("写真").to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let renamed = dir.path().join("shashin").join("ryokou");
+    assert!(renamed.is_dir(), "depth 1 should be renamed");
+    assert!(
+        renamed.join("海.jpg").exists(),
+        "depth 2 should be left alone"
+    );
+}
+
+#[test]
+fn json_format_lists_statuses() {
+    let dir = create_temp_dir();
+    let file = dir.path().join("テスト.txt");
+    let ascii = dir.path().join("hello.txt");
+    fs::write(&file, "").unwrap();
+    fs::write(&ascii, "").unwrap();
+
+    let output = romv()
+        .args([
+            "--format",
+            "json",
+            file.to_str().unwrap(),
+            ascii.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"status\": \"ready\""), "got: {stdout}");
+    assert!(
+        stdout.contains("\"reason\": \"unchanged\""),
+        "got: {stdout}"
+    );
+    assert!(!stdout.contains("->"), "should not mix in human output");
+}
+
+#[test]
+fn execute_edited_plan() {
+    let dir = create_temp_dir();
+    let file = dir.path().join("テスト.txt");
+    fs::write(&file, "content").unwrap();
+
+    let output = romv()
+        .args(["--format", "tsv", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Review and edit the exported plan before applying it
+    let plan = String::from_utf8(output.stdout)
+        .unwrap()
+        .replace("tesuto.txt", "test.txt");
+    let plan_file = dir.path().join("plan.tsv");
+    fs::write(&plan_file, plan).unwrap();
+
+    let output = romv()
+        .args(["-y", "--plan", plan_file.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(!file.exists());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("test.txt")).unwrap(),
+        "content"
+    );
+}
+
+#[test]
+fn on_conflict_skip() {
+    let dir = create_temp_dir();
+    let source = dir.path().join("テスト.txt");
+    let target = dir.path().join("tesuto.txt");
+    fs::write(&source, "source").unwrap();
+    fs::write(&target, "existing").unwrap();
+
+    let output = romv()
+        .args(["-y", "--on-conflict", "skip", source.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "skipped conflicts are not errors");
+    assert!(source.exists());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "existing");
+}
+
+#[test]
+fn on_conflict_suffix() {
+    let dir = create_temp_dir();
+    let existing = dir.path().join("tesuto.txt");
+    fs::write(&existing, "existing").unwrap();
+    // Both convert to tesuto.txt, which is also taken on disk
+    let katakana = dir.path().join("テスト.txt");
+    let hiragana = dir.path().join("てすと.txt");
+    fs::write(&katakana, "katakana").unwrap();
+    fs::write(&hiragana, "hiragana").unwrap();
+
+    let output = romv()
+        .args([
+            "-y",
+            "--on-conflict",
+            "suffix",
+            katakana.to_str().unwrap(),
+            hiragana.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(fs::read_to_string(&existing).unwrap(), "existing");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("tesuto_1.txt")).unwrap(),
+        "katakana"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("tesuto_2.txt")).unwrap(),
+        "hiragana"
+    );
+}
+
+#[test]
+fn on_conflict_suffix_respects_max_length() {
+    let dir = create_temp_dir();
+    let source = dir.path().join("会議資料.pdf");
+    fs::write(&source, "new").unwrap();
+    fs::write(dir.path().join("kaigishiryou.pdf"), "old").unwrap();
+
+    let output = romv()
+        .args([
+            "-y",
+            "-v",
+            "--on-conflict",
+            "suffix",
+            "--max-length",
+            "16",
+            source.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("kaigishiry_1.pdf")).unwrap(),
+        "new"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("18 bytes, limit 16"), "{stderr}");
+}
+
+#[test]
+fn on_conflict_overwrite_backup() {
+    let dir = create_temp_dir();
+    let source = dir.path().join("テスト.txt");
+    let target = dir.path().join("tesuto.txt");
+    fs::write(&source, "source").unwrap();
+    fs::write(&target, "existing").unwrap();
+
+    let output = romv()
+        .args([
+            "-y",
+            "--on-conflict",
+            "overwrite-backup",
+            source.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(!source.exists());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "source");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("tesuto.txt~")).unwrap(),
+        "existing"
+    );
+}
+
+#[test]
+fn plan_keeps_backups() {
+    let dir = create_temp_dir();
+    let source = dir.path().join("テスト.txt");
+    let target = dir.path().join("tesuto.txt");
+    fs::write(&source, "source").unwrap();
+    fs::write(&target, "existing").unwrap();
+
+    let output = romv()
+        .args([
+            "--format",
+            "ndjson",
+            "--on-conflict",
+            "overwrite-backup",
+            source.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let plan_file = dir.path().join("plan.ndjson");
+    fs::write(&plan_file, &output.stdout).unwrap();
+
+    let output = romv()
+        .args(["-y", "--plan", plan_file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(fs::read_to_string(&target).unwrap(), "source");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("tesuto.txt~")).unwrap(),
+        "existing"
+    );
+}
+
 #[test]
 fn nonexistent_source() {
-    let output = Command::new(romv_bin())
+    let output = romv()
         .args(["-y", "/tmp/definitely_does_not_exist_テスト.txt"])
         .output()
         .unwrap();
@@ -225,3 +530,645 @@ fn nonexistent_source() {
         "should report source not found, got: {stderr}"
     );
 }
+
+#[test]
+fn undo_reverts_last_batch() {
+    let dir = create_temp_dir();
+    let state = dir.path().join("state");
+    let file = dir.path().join("テスト.txt");
+    fs::write(&file, "content").unwrap();
+
+    let output = romv()
+        .env("XDG_STATE_HOME", &state)
+        .args(["-y", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.path().join("tesuto.txt").exists());
+
+    let journal = state.join("romv").join("journal.jsonl");
+    let record = fs::read_to_string(&journal).unwrap();
+    assert!(record.contains("\"timestamp\""), "journal: {record}");
+    assert!(record.contains("\"cwd\""), "journal: {record}");
+
+    // Dry-run by default
+    let output = romv()
+        .env("XDG_STATE_HOME", &state)
+        .arg("undo")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.path().join("tesuto.txt").exists());
+
+    let output = romv()
+        .env("XDG_STATE_HOME", &state)
+        .args(["undo", "-y"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+    assert!(!dir.path().join("tesuto.txt").exists());
+    assert_eq!(fs::read_to_string(&journal).unwrap(), "");
+}
+
+#[test]
+fn undo_without_journal() {
+    let dir = create_temp_dir();
+
+    let output = romv()
+        .env("XDG_STATE_HOME", dir.path())
+        .arg("undo")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Nothing to undo."));
+
+    let output = romv()
+        .args(["undo", "--journal"])
+        .arg(dir.path().join("missing.jsonl"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn undo_no_clobber() {
+    let dir = create_temp_dir();
+    let journal = dir.path().join("journal.jsonl");
+    let file = dir.path().join("テスト.txt");
+    fs::write(&file, "").unwrap();
+
+    let output = romv()
+        .env("XDG_STATE_HOME", dir.path())
+        .args(["-y", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    fs::rename(dir.path().join("romv").join("journal.jsonl"), &journal).unwrap();
+
+    // Something else took the original name in the meantime
+    fs::write(&file, "new").unwrap();
+
+    let output = romv()
+        .args(["undo", "-y", "--journal", journal.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+    assert!(dir.path().join("tesuto.txt").exists());
+}
+
+#[test]
+fn plan_swaps_two_files() {
+    let dir = create_temp_dir();
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    fs::write(&a, "a").unwrap();
+    fs::write(&b, "b").unwrap();
+    let plan_file = dir.path().join("plan.tsv");
+    fs::write(
+        &plan_file,
+        format!(
+            "{a}\t{b}\n{b}\t{a}\n",
+            a = a.to_str().unwrap(),
+            b = b.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    let output = romv()
+        .args(["-y", "--plan", plan_file.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(fs::read_to_string(&a).unwrap(), "b");
+    assert_eq!(fs::read_to_string(&b).unwrap(), "a");
+    let leftovers = fs::read_dir(dir.path())
+        .unwrap()
+        .filter(|e| {
+            e.as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .ends_with(".tmp")
+        })
+        .count();
+    assert_eq!(leftovers, 0, "temporary names are cleaned up");
+}
+
+#[test]
+fn failed_chain_reports_where_file_was_left() {
+    let dir = create_temp_dir();
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    fs::write(&a, "a").unwrap();
+    fs::write(&b, "b").unwrap();
+    // b.txt is moved aside for a.txt, then cannot reach its own target
+    let plan_file = dir.path().join("plan.tsv");
+    fs::write(
+        &plan_file,
+        format!(
+            "{}\t{}\n{}\t{}\n",
+            a.display(),
+            b.display(),
+            b.display(),
+            dir.path().join("missing/c.txt").display()
+        ),
+    )
+    .unwrap();
+
+    let output = romv()
+        .args(["-y", "--plan", plan_file.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(&b).unwrap(), "a");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let (_, left_at) = stderr.split_once("(file left at ").expect(&stderr);
+    let temp = left_at.split_once(')').unwrap().0;
+    assert_eq!(fs::read_to_string(temp).unwrap(), "b");
+}
+
+#[test]
+fn chain_into_vacated_name() {
+    let dir = create_temp_dir();
+    // テスト.txt -> tesuto.txt while tesuto.txt itself moves away
+    let source = dir.path().join("テスト.txt");
+    let occupied = dir.path().join("tesuto.txt");
+    fs::write(&source, "source").unwrap();
+    fs::write(&occupied, "occupied").unwrap();
+    let plan_file = dir.path().join("plan.tsv");
+    fs::write(
+        &plan_file,
+        format!(
+            "{}\t{}\n{}\t{}\n",
+            source.to_str().unwrap(),
+            occupied.to_str().unwrap(),
+            occupied.to_str().unwrap(),
+            dir.path().join("old.txt").to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    let output = romv()
+        .args(["-y", "--plan", plan_file.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(!source.exists());
+    assert_eq!(fs::read_to_string(&occupied).unwrap(), "source");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("old.txt")).unwrap(),
+        "occupied"
+    );
+}
+
+#[test]
+fn config_profile_with_cli_override() {
+    let dir = create_temp_dir();
+    fs::write(
+        dir.path().join(".romv.toml"),
+        "case = \"snake\"\n[profiles.photos]\ncase = \"kebab\"\nsystem = \"kunrei\"\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("新しい写真.jpg"), "").unwrap();
+
+    let output = romv()
+        .current_dir(dir.path())
+        .args(["--profile", "photos", "-y", "新しい写真.jpg"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(dir.path().join("atarasii-syasin.jpg").exists());
+
+    fs::write(dir.path().join("新しい写真.jpg"), "").unwrap();
+    let output = romv()
+        .current_dir(dir.path())
+        .args([
+            "--profile",
+            "photos",
+            "--case",
+            "pascal",
+            "-y",
+            "新しい写真.jpg",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(dir.path().join("AtarasiiSyasin.jpg").exists());
+}
+
+#[test]
+fn config_values_give_way_to_command_line() {
+    let dir = create_temp_dir();
+    let photos = dir.path().join("写真");
+    fs::create_dir_all(photos.join("2024年")).unwrap();
+    fs::write(photos.join("海.jpg"), "").unwrap();
+    fs::write(photos.join("2024年/山.jpg"), "").unwrap();
+
+    let output = romv()
+        .current_dir(dir.path())
+        .args(["-y", "--print0", "--format", "ndjson", "写真/海.jpg"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    assert!(photos.join("海.jpg").exists());
+
+    // --print0 on the command line wins over a config format
+    fs::write(dir.path().join(".romv.toml"), "format = \"ndjson\"\n").unwrap();
+    let output = romv()
+        .current_dir(dir.path())
+        .args(["-y", "--print0", "写真/海.jpg"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"\xe5\x86\x99\xe7\x9c\x9f/umi.jpg\0");
+
+    // A config max-depth only applies to recursive runs
+    fs::write(dir.path().join(".romv.toml"), "max-depth = 1\n").unwrap();
+    let output = romv()
+        .current_dir(dir.path())
+        .args(["-y", "写真/2024年"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(photos.join("2024nen/山.jpg").exists());
+
+    let output = romv()
+        .current_dir(dir.path())
+        .args(["-y", "--max-depth", "1", "写真"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+
+    fs::write(dir.path().join(".romv.toml"), "recursive = true\n").unwrap();
+    let output = romv()
+        .current_dir(dir.path())
+        .args(["-y", "--max-depth", "1", "写真"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(dir.path().join("shashin/2024nen/山.jpg").exists());
+}
+
+#[test]
+fn config_show_prints_effective_settings() {
+    let dir = create_temp_dir();
+    let config_home = dir.path().join("config");
+    fs::create_dir_all(config_home.join("romv")).unwrap();
+    fs::write(
+        config_home.join("romv/config.toml"),
+        "separator = \"-\"\n[profiles.photos]\ncase = \"kebab\"\n",
+    )
+    .unwrap();
+
+    let output = romv()
+        .current_dir(dir.path())
+        .env("XDG_CONFIG_HOME", &config_home)
+        .args(["config", "show", "--profile", "photos"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("separator = \"-\""), "{stdout}");
+    assert!(stdout.contains("case = \"kebab\""), "{stdout}");
+    assert!(stdout.contains("system = \"hepburn\""), "{stdout}");
+}
+
+#[test]
+fn full_path_renames_directories() {
+    let dir = create_temp_dir();
+    let year = dir.path().join("写真/2024年");
+    fs::create_dir_all(&year).unwrap();
+    fs::write(year.join("旅行.jpg"), "trip").unwrap();
+    fs::write(year.join("海.jpg"), "sea").unwrap();
+
+    let output = romv()
+        .current_dir(dir.path())
+        .args([
+            "-y",
+            "--full-path",
+            "写真/2024年/旅行.jpg",
+            "./写真/2024年/海.jpg",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    let renamed = dir.path().join("shashin/2024nen");
+    assert_eq!(
+        fs::read_to_string(renamed.join("ryokou.jpg")).unwrap(),
+        "trip"
+    );
+    assert_eq!(fs::read_to_string(renamed.join("umi.jpg")).unwrap(), "sea");
+    assert!(!dir.path().join("写真").exists());
+}
+
+#[test]
+fn null_separated_input_and_output() {
+    let dir = create_temp_dir();
+    let newline = dir.path().join("改行\nファイル.txt");
+    let plain = dir.path().join("テスト.txt");
+    fs::write(&newline, "").unwrap();
+    fs::write(&plain, "").unwrap();
+
+    let mut child = romv()
+        .args(["-0", "--print0", "-y"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = format!("{}\0{}\0", newline.display(), plain.display());
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    let expected = dir.path().join("kaigyou\nfairu.txt");
+    assert!(expected.exists());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        format!(
+            "{}\0{}\0",
+            expected.display(),
+            dir.path().join("tesuto.txt").display()
+        )
+    );
+}
+
+#[test]
+fn print0_follows_renamed_directories() {
+    let dir = create_temp_dir();
+    let photos = dir.path().join("写真");
+    fs::create_dir(&photos).unwrap();
+    fs::write(photos.join("海.jpg"), "").unwrap();
+
+    let output = romv()
+        .args(["-r", "-y", "--print0"])
+        .arg(&photos)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let targets: Vec<&str> = stdout.split_terminator('\0').collect();
+    let renamed = dir.path().join("shashin");
+    assert_eq!(
+        targets,
+        [
+            renamed.join("umi.jpg").to_str().unwrap(),
+            renamed.to_str().unwrap()
+        ]
+    );
+    assert!(targets.iter().all(|t| fs::exists(t).unwrap()));
+}
+
+#[test]
+fn from_encoding_renames_legacy_names() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = create_temp_dir();
+    // "テスト.txt" in Shift_JIS
+    let name = std::ffi::OsStr::from_bytes(b"\x83\x65\x83\x58\x83\x67.txt");
+    let source = dir.path().join(name);
+    fs::write(&source, "legacy").unwrap();
+
+    let output = romv().arg(&source).output().unwrap();
+    assert!(output.status.success());
+    assert!(source.exists(), "skipped without --from-encoding");
+
+    let state = dir.path().join("state");
+    let output = romv()
+        .env("XDG_STATE_HOME", &state)
+        .args(["-y", "--from-encoding", "auto"])
+        .arg(&source)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+    assert!(!source.exists());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("tesuto.txt")).unwrap(),
+        "legacy"
+    );
+
+    // The journal keeps the original bytes, so the rename can be undone
+    let output = romv()
+        .env("XDG_STATE_HOME", &state)
+        .args(["undo", "-y"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(fs::read_to_string(&source).unwrap(), "legacy");
+}
+
+#[test]
+fn from_encoding_reads_legacy_names_from_stdin() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = create_temp_dir();
+    // "テスト.txt" in Shift_JIS
+    let name = std::ffi::OsStr::from_bytes(b"\x83\x65\x83\x58\x83\x67.txt");
+    let source = dir.path().join(name);
+    fs::write(&source, "legacy").unwrap();
+
+    let mut child = romv()
+        .args(["-y", "--from-encoding", "sjis"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(source.as_os_str().as_bytes()).unwrap();
+    stdin.write_all(b"\n").unwrap();
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(!source.exists());
+    assert!(dir.path().join("tesuto.txt").exists());
+}
+
+#[test]
+fn normalize_only_composes_nfd_names() {
+    let dir = create_temp_dir();
+    let nfd = dir.path().join("か\u{3099}いこく.txt");
+    fs::write(&nfd, "").unwrap();
+
+    let output = romv()
+        .args(["-y", "--normalize-only", nfd.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(!nfd.exists());
+    assert!(dir.path().join("がいこく.txt").exists());
+}
+
+#[test]
+fn strict_ascii_rejects_leftover_characters() {
+    let dir = create_temp_dir();
+    let file = dir.path().join("東京.txt");
+    fs::write(&file, "").unwrap();
+
+    let output = romv()
+        .args([
+            "-y",
+            "--strict-ascii",
+            "--long-vowels",
+            "macron",
+            file.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("'ō' (U+014D)"), "{stderr}");
+    assert!(file.exists());
+}
+
+#[test]
+fn scripts_transliterate_other_languages() {
+    let dir = create_temp_dir();
+    for name in ["한국어.txt", "Москва.txt", "café.txt"] {
+        fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let paths: Vec<_> = ["한국어.txt", "Москва.txt", "café.txt"]
+        .iter()
+        .map(|name| dir.path().join(name))
+        .collect();
+    let output = romv()
+        .args(["-y", "--scripts", "hangul,cyrillic,latin"])
+        .args(&paths)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    for name in ["hangugeo.txt", "Moskva.txt", "cafe.txt"] {
+        assert!(dir.path().join(name).exists(), "{name}");
+    }
+}
+
+#[test]
+fn extensions_romanized_and_lowercased() {
+    let dir = create_temp_dir();
+    let archive = dir.path().join("データ.tar.gz");
+    let photo = dir.path().join("写真.JPG");
+    let backup = dir.path().join("資料.バックアップ");
+    for file in [&archive, &photo, &backup] {
+        fs::write(file, "").unwrap();
+    }
+
+    let output = romv()
+        .args(["-y", "--case", "kebab", "--romanize-ext", "--lowercase-ext"])
+        .args([&archive, &photo, &backup])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    for name in ["deeta.tar.gz", "shashin.jpg", "shiryou.bakkuappu"] {
+        assert!(dir.path().join(name).exists(), "{name}");
+    }
+}
+
+#[test]
+fn sanitize_reports_each_rule() {
+    let dir = create_temp_dir();
+    let file = dir.path().join("会議: 明日?.txt");
+    fs::write(&file, "").unwrap();
+
+    let output = romv()
+        .args(["-y", "-v", "--sanitize", "windows", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(dir.path().join("kaigi_ashita.txt").exists());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("replaced unsafe ':', '?'"), "{stderr}");
+    assert!(stderr.contains("collapsed repeated separators"), "{stderr}");
+}
+
+#[test]
+fn max_length_truncates_or_skips() {
+    let dir = create_temp_dir();
+    let long = dir.path().join("会議資料 最終版.pdf");
+    let hopeless = dir.path().join("写真.verylongextension");
+    fs::write(&long, "").unwrap();
+    fs::write(&hopeless, "").unwrap();
+
+    let output = romv()
+        .args(["-y", "--max-length", "16"])
+        .args([&long, &hopeless])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(dir.path().join("kaigishiryou.pdf").exists());
+    assert!(hopeless.exists());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("name too long"), "{stderr}");
+}
+
+#[test]
+fn map_overrides_punctuation() {
+    let dir = create_temp_dir();
+    let file = dir.path().join("【速報】会議.txt");
+    fs::write(&file, "").unwrap();
+
+    let output = romv()
+        .args(["-y", "--map", "【=(", "--map", "】=)"])
+        .arg(&file)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(dir.path().join("(sokuhou)kaigi.txt").exists());
+
+    let output = romv().args(["--map", "【】=[]", "x"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn filters_are_counted_apart_from_skips() {
+    let dir = create_temp_dir();
+    let sub = dir.path().join("写真");
+    fs::create_dir(&sub).unwrap();
+    for name in ["旅行.jpg", "旅行_thumb.jpg", "メモ.txt"] {
+        fs::write(sub.join(name), "").unwrap();
+    }
+
+    let output = romv()
+        .args(["-y", "-v", "-r", "--type", "f", "--include", "*.jpg"])
+        .args(["--exclude", "*_thumb.*", "--match", "^旅"])
+        .arg(&sub)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(sub.join("ryokou.jpg").exists());
+    assert!(sub.join("旅行_thumb.jpg").exists());
+    assert!(sub.join("メモ.txt").exists());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("1 renamed, 0 errors, 0 skipped, 3 filtered"),
+        "{stderr}"
+    );
+}